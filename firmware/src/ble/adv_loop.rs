@@ -1,8 +1,8 @@
 use super::{dfu::DfuConfig, server::GloveServer};
 use crate::{
-    ble::{bonder::Bonder, dfu::NrfDfuServiceEvent},
+    ble::{bonder::Bonder, dfu::NrfDfuServiceEvent, hid::HidServiceEvent},
     interboard::{channel::COMMANDS_TO_OTHER_SIDE, THIS_SIDE_MESSAGE_BUS},
-    state::with_advertising,
+    state::{with_advertising, BLE_HID_HOST, BLE_HOST_LEDS},
 };
 use embassy_boot::AlignedBuffer;
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
@@ -166,8 +166,11 @@ async fn handle_connection(
         core::future::pending::<Never>().await
     };
 
+    let peer = conn.peer_address().bytes;
+
     let hid_processor = async {
         if let Some(hid) = server.hid.as_ref() {
+            BLE_HID_HOST.set(Some(peer));
             hid.send_reports(&conn).await
         } else {
             // if there's no hid server this one should run forever
@@ -240,6 +243,11 @@ async fn handle_connection(
                     );
                 }
             }
+            crate::ble::server::GloveServerEvent::HID(HidServiceEvent::OutputReportWrite(leds)) => {
+                if BLE_HID_HOST.current() == Some(peer) {
+                    BLE_HOST_LEDS.set(leds);
+                }
+            }
             crate::ble::server::GloveServerEvent::HID(_) => {
                 // there's nothing to do here
             }
@@ -280,5 +288,10 @@ async fn handle_connection(
     })
     .await;
 
+    if BLE_HID_HOST.current() == Some(peer) {
+        BLE_HID_HOST.set(None);
+        BLE_HOST_LEDS.set(0);
+    }
+
     crate::log::debug!("Device disconnected");
 }
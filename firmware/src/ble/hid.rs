@@ -27,6 +27,17 @@ pub struct HidService {
     )]
     pub input_report: <NKROBootKeyboardReport as PackedStruct>::ByteArray,
 
+    /// The host's keyboard LEDs, caps lock and so on
+    #[characteristic(
+        uuid = "2A4D",
+        security = "justworks",
+        read,
+        write,
+        write_without_response,
+        descriptor(uuid = "2908", security = "justworks", value = "[0, 2]")
+    )]
+    pub output_report: u8,
+
     #[characteristic(
         uuid = "2A4A",
         security = "justworks",
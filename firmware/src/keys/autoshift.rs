@@ -0,0 +1,158 @@
+use embassy_time::{Duration, Instant};
+use keyberon::{action::Action, key_code::KeyCode, layout::Event};
+
+use super::{
+    chord::{Key, CHORD_TIMEOUT},
+    layout::LAYERS,
+};
+
+pub struct AutoShiftConfig {
+    /// Keys held for longer than this are sent shifted
+    pub timeout: Duration,
+    /// Keys (as layout positions) that are never auto-shifted
+    pub excluded: &'static [Key],
+}
+
+pub const AUTO_SHIFT: AutoShiftConfig = AutoShiftConfig {
+    timeout: Duration::from_millis(175),
+    excluded: &[],
+};
+
+fn is_shiftable(k: KeyCode) -> bool {
+    (KeyCode::A as u8..=KeyCode::Z as u8).contains(&(k as u8))
+        || (KeyCode::Kb1 as u8..=KeyCode::Kb0 as u8).contains(&(k as u8))
+        || (KeyCode::Minus as u8..=KeyCode::Slash as u8).contains(&(k as u8))
+}
+
+struct Pending {
+    key: Key,
+    keycode: KeyCode,
+    pressed_at: Instant,
+}
+
+/// Auto shift delays the press of shiftable keys until they are either
+/// released (a tap, sent unshifted) or held past the timeout (sent shifted)
+///
+/// Only keys bound directly to a keycode take part, so hold-taps and other
+/// actions pass straight through to keyberon. Presses are always forwarded in
+/// the order they happened so keyberon sees the same sequence it would have
+/// without auto shift.
+pub struct AutoShift {
+    enabled: bool,
+    chord_keys: &'static phf::Map<[u8; 2], &'static [usize]>,
+    pending: Option<Pending>,
+    shifted: heapless::Vec<(Key, KeyCode), 8>,
+}
+
+pub type Events = heapless::Vec<Event, 3>;
+
+impl AutoShift {
+    pub fn new(chord_keys: &'static phf::Map<[u8; 2], &'static [usize]>) -> Self {
+        Self {
+            enabled: false,
+            chord_keys,
+            pending: None,
+            shifted: heapless::Vec::new(),
+        }
+    }
+
+    pub fn toggle(&mut self) -> Events {
+        self.enabled = !self.enabled;
+
+        crate::log::info!("Auto shift enabled: {}", self.enabled);
+
+        // resolve anything in flight as a normal press
+        self.flush()
+    }
+
+    fn eligible(&self, (x, y): Key, layer: usize) -> Option<KeyCode> {
+        if !self.enabled || AUTO_SHIFT.excluded.contains(&(x, y)) {
+            return None;
+        }
+
+        match LAYERS[layer][x as usize][y as usize] {
+            Action::KeyCode(k) if is_shiftable(k) => Some(k),
+            _ => None,
+        }
+    }
+
+    fn flush(&mut self) -> Events {
+        let mut out = Events::new();
+
+        if let Some(p) = self.pending.take() {
+            let _ = out.push(Event::Press(p.key.0, p.key.1));
+        }
+
+        out
+    }
+
+    /// Process an event before it goes into the layout
+    pub fn event(&mut self, evt: Event, layer: usize) -> Events {
+        match evt {
+            Event::Press(x, y) => {
+                let mut out = self.flush();
+
+                if let Some(keycode) = self.eligible((x, y), layer) {
+                    // presses of keys that are part of chords were held back
+                    // by the chording engine before reaching us
+                    let delay = if self.chord_keys.contains_key(&[x, y]) {
+                        CHORD_TIMEOUT
+                    } else {
+                        Duration::from_ticks(0)
+                    };
+
+                    self.pending = Some(Pending {
+                        key: (x, y),
+                        keycode,
+                        pressed_at: Instant::now().checked_sub(delay).unwrap_or(Instant::MIN),
+                    });
+                } else {
+                    let _ = out.push(evt);
+                }
+
+                out
+            }
+            Event::Release(x, y) => {
+                let mut out = Events::new();
+
+                if self.pending.as_ref().is_some_and(|p| p.key == (x, y)) {
+                    // released before the timeout, send an unshifted tap
+                    self.pending = None;
+                    let _ = out.push(Event::Press(x, y));
+                } else {
+                    self.shifted.retain(|(k, _)| *k != (x, y));
+                }
+
+                let _ = out.push(evt);
+
+                out
+            }
+        }
+    }
+
+    /// Called on every tick, sends the pending key if it has been held long enough
+    pub fn tick(&mut self) -> Events {
+        let mut out = Events::new();
+
+        let Some(p) = self
+            .pending
+            .take_if(|p| p.pressed_at.elapsed() >= AUTO_SHIFT.timeout)
+        else {
+            return out;
+        };
+
+        let _ = self.shifted.push((p.key, p.keycode));
+        let _ = out.push(Event::Press(p.key.0, p.key.1));
+
+        out
+    }
+
+    /// Apply shift to the keycodes of keys that were held past the timeout
+    pub fn process(&self, keycodes: &mut heapless::Vec<KeyCode, 24>) {
+        let any_shifted = self.shifted.iter().any(|(_, k)| keycodes.contains(k));
+
+        if any_shifted && !keycodes.contains(&KeyCode::LShift) {
+            let _ = keycodes.push(KeyCode::LShift);
+        }
+    }
+}
@@ -47,6 +47,9 @@ pub struct ChordingEngine {
     // after firing a release of a chord, ignore the following key releases
     ignored_releases: heapless::Vec<Key, 16>,
     last_press: Instant,
+
+    // index of the last chord to fire, if nobody has looked at it yet
+    triggered: Option<usize>,
 }
 
 impl ChordingEngine {
@@ -56,9 +59,15 @@ impl ChordingEngine {
             held_keys: heapless::Vec::new(),
             ignored_releases: heapless::Vec::new(),
             last_press: Instant::now(),
+            triggered: None,
         }
     }
 
+    /// The index of the chord that last fired, once
+    pub fn take_triggered(&mut self) -> Option<usize> {
+        self.triggered.take()
+    }
+
     pub fn purge(&mut self) -> heapless::Vec<Key, 16> {
         for &(x, y) in &self.held_keys {
             if let Some(appropriate_chords) = self.chorder.key_chord_map.get(&[x, y]) {
@@ -113,6 +122,7 @@ impl ChordingEngine {
                             // chord became active with the key, clear out
                             // held_keys and emit the chord
                             self.held_keys.clear();
+                            self.triggered = Some(chord_idx);
 
                             return heapless::Vec::from_iter(
                                 chord
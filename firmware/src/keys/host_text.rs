@@ -0,0 +1,106 @@
+use core::cell::RefCell;
+
+use embassy_sync::{
+    blocking_mutex::{raw::ThreadModeRawMutex, Mutex},
+    channel::Channel,
+    signal::Signal,
+};
+use embassy_time::{Duration, Timer};
+
+use super::text;
+
+/// Longest text the host can send in one go
+pub const MAX_HOST_TEXT: usize = 512;
+
+/// Minimum time between typed characters, input methods on the host drop
+/// characters if we go too fast
+const CHAR_INTERVAL: Duration = Duration::from_millis(15);
+
+struct Assembly {
+    id: u8,
+    next_seq: u8,
+    buf: heapless::Vec<u8, MAX_HOST_TEXT>,
+}
+
+static ASSEMBLY: Mutex<ThreadModeRawMutex, RefCell<Option<Assembly>>> =
+    Mutex::new(RefCell::new(None));
+
+static READY: Channel<ThreadModeRawMutex, heapless::String<MAX_HOST_TEXT>, 1> = Channel::new();
+
+static CANCEL: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+/// Collect a chunk of text sent by the host, once the last chunk arrives the
+/// text is queued for typing
+pub fn receive_chunk(id: u8, seq: u8, last: bool, chunk: &[u8]) {
+    let complete = ASSEMBLY.lock(|a| {
+        let mut a = a.borrow_mut();
+
+        if seq == 0 {
+            *a = Some(Assembly {
+                id,
+                next_seq: 0,
+                buf: heapless::Vec::new(),
+            });
+        }
+
+        let Some(assembly) = a.as_mut().filter(|x| x.id == id && x.next_seq == seq) else {
+            crate::log::warn!("Dropping out of order text chunk ({}, {})", id, seq);
+            *a = None;
+            return None;
+        };
+
+        if assembly.buf.extend_from_slice(chunk).is_err() {
+            crate::log::warn!("Text from host is longer than {} bytes", MAX_HOST_TEXT);
+            *a = None;
+            return None;
+        }
+
+        assembly.next_seq = assembly.next_seq.wrapping_add(1);
+
+        if last {
+            a.take().map(|x| x.buf)
+        } else {
+            None
+        }
+    });
+
+    let Some(buf) = complete else {
+        return;
+    };
+
+    let Ok(text) = heapless::String::from_utf8(buf) else {
+        crate::log::warn!("Text from host wasn't valid UTF-8");
+        return;
+    };
+
+    if READY.try_send(text).is_err() {
+        crate::log::warn!("Dropping text from host, still typing the last one");
+    }
+}
+
+/// Stop typing and forget any text from the host
+pub fn cancel() {
+    ASSEMBLY.lock(|a| a.borrow_mut().take());
+    while READY.try_receive().is_ok() {}
+    CANCEL.signal(());
+}
+
+#[embassy_executor::task]
+pub async fn host_text_task() {
+    loop {
+        let msg = READY.receive().await;
+        CANCEL.reset();
+
+        let layout = text::current_layout().await;
+
+        for c in msg.chars() {
+            if CANCEL.signaled() {
+                crate::log::info!("Typing text from host cancelled");
+                break;
+            }
+
+            text::type_char(layout, c).await;
+            Timer::after(CHAR_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use keyberon::key_code::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::Watch;
+
+use super::oneshot::modifier_bit;
+
+/// Keyboard state shown on the LEDs, worked out on the master side and sent to
+/// the other side whenever it changes
+pub static INDICATORS: Watch<Indicators> = Watch::new(Indicators::new());
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub struct Indicators {
+    /// Modifiers (as HID modifier bits) currently held
+    pub held_mods: u8,
+    pub caps_word: bool,
+    /// Caps lock as set by the host key presses are going to
+    pub caps_lock: bool,
+    /// Address of the BLE host key presses go to, `None` when using USB
+    pub ble_host: Option<[u8; 6]>,
+}
+
+impl Default for Indicators {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Indicators {
+    pub const fn new() -> Self {
+        Self {
+            held_mods: 0,
+            caps_word: false,
+            caps_lock: false,
+            ble_host: None,
+        }
+    }
+}
+
+/// The modifiers held in a set of keycodes, as HID modifier bits
+pub fn held_mods(keycodes: &[KeyCode]) -> u8 {
+    keycodes.iter().fold(0, |mods, &k| mods | modifier_bit(k))
+}
@@ -0,0 +1,61 @@
+//! Default colours for keys on each layer, used for any row the host hasn't
+//! set its own colours for
+//!
+//! Positions are keymap coordinates, the same as [`super::layout::LAYERS`].
+//! Keys are coloured by what they're bound to, so numbers stand out on the
+//! number layer and arrows on the navigation layer. The base layer is left to
+//! the animation.
+
+use keyberon::{action::Action, key_code::KeyCode};
+use shared::rgb::Rgb;
+
+use super::{layout::LAYERS, switches::LAYOUT_COLS};
+
+pub type KeyColourRow = [Option<Rgb>; LAYOUT_COLS];
+
+const NUMBER: Rgb = Rgb {
+    r: 40,
+    g: 255,
+    b: 60,
+};
+
+const FUNCTION: Rgb = Rgb {
+    r: 160,
+    g: 0,
+    b: 255,
+};
+
+const ARROW: Rgb = Rgb {
+    r: 0,
+    g: 200,
+    b: 255,
+};
+
+const NAVIGATION: Rgb = Rgb {
+    r: 0,
+    g: 80,
+    b: 255,
+};
+
+fn colour_of(k: KeyCode) -> Option<Rgb> {
+    use KeyCode::*;
+
+    match k {
+        Kb1 | Kb2 | Kb3 | Kb4 | Kb5 | Kb6 | Kb7 | Kb8 | Kb9 | Kb0 => Some(NUMBER),
+        F1 | F2 | F3 | F4 | F5 | F6 | F7 | F8 | F9 | F10 | F11 | F12 => Some(FUNCTION),
+        Up | Down | Left | Right => Some(ARROW),
+        Home | End | PgUp | PgDown => Some(NAVIGATION),
+        _ => None,
+    }
+}
+
+pub fn default_row(layer: usize, row: usize) -> KeyColourRow {
+    if layer == 0 {
+        return [None; LAYOUT_COLS];
+    }
+
+    core::array::from_fn(|col| match LAYERS[layer][row][col] {
+        Action::KeyCode(k) => colour_of(k),
+        _ => None,
+    })
+}
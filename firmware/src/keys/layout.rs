@@ -42,19 +42,19 @@ pub static LAYERS: ::keyberon::layout::Layers<12, 10, 3, super::CustomEvent> = [
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
   ],
   [
-    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::Custom(super::CustomEvent::ToggleAutoShift), ::keyberon::action::Action::Custom(super::CustomEvent::Macro(&[super::macros::MacroStep::TapWith(&[::keyberon::key_code::KeyCode::LCtrl], ::keyberon::key_code::KeyCode::A), super::macros::MacroStep::TapWith(&[::keyberon::key_code::KeyCode::LCtrl], ::keyberon::key_code::KeyCode::C)])), ::keyberon::action::Action::Custom(super::CustomEvent::CapsWord), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::Custom(super::CustomEvent::NumWord), ::keyberon::action::Action::Custom(super::CustomEvent::OneShotMod(::keyberon::key_code::KeyCode::LShift)), ::keyberon::action::Action::Custom(super::CustomEvent::CycleUnicodeMode), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F1), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F2), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F3), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F4), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F5), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F6), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F7), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F8), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F9), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F10), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb1].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb2].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::LBracket].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::RBracket].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Bslash].as_slice()), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Grave), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Grave].as_slice()), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Bslash), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Quote].as_slice()), ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::LShift), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb3].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb4].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb9].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb0].as_slice()), ::keyberon::action::Action::Custom(super::CustomEvent::TypeUnicode("𓆏")), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Equal].as_slice()), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Minus), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Slash), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb8].as_slice()), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Quote), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::RShift), ],
     [::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::LCtrl), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb5].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb6].as_slice()), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::LBracket), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::RBracket), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Kb7].as_slice()), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Equal), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Comma), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Dot), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LShift, ::keyberon::key_code::KeyCode::Minus].as_slice()), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::RCtrl), ],
-    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::Custom(super::CustomEvent::OneShotLayer(2)), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::LShift), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::LGui), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::LAlt), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::RAlt), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::RGui), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::RShift), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Equal), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Tab), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Space), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Space), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Enter), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
   ],
   [
-    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::Custom(super::CustomEvent::BrightnessDown), ::keyberon::action::Action::Custom(super::CustomEvent::BrightnessUp), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::Custom(super::CustomEvent::ToggleBrightness), ::keyberon::action::Action::Custom(super::CustomEvent::NextAnimation), ::keyberon::action::Action::Custom(super::CustomEvent::SetAnimation(::shared::rgb::AnimationChoice::Snow)), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::VolUp), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::VolDown), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb1), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb2), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb3), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb4), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb5), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb6), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb7), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb8), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb9), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Kb0), ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::LShift), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F1), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F2), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F3), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F4), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::F5), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Left), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Down), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Up), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Right), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::RShift), ],
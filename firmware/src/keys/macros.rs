@@ -0,0 +1,117 @@
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use embassy_time::Timer;
+use keyberon::key_code::KeyCode;
+
+use super::{press_synthetic_keycodes, text, unicode};
+
+/// A single step of a macro, macros are bound in the keymap with
+/// `Action::Custom(CustomEvent::Macro(&[...]))`
+#[derive(Clone, Copy)]
+pub enum MacroStep {
+    /// Press a key, it stays held until released or the macro finishes
+    Press(KeyCode),
+    /// Release a key previously held with [`MacroStep::Press`]
+    Release(KeyCode),
+    /// Press and release a key
+    Tap(KeyCode),
+    /// Press and release a key with some modifiers held
+    TapWith(&'static [KeyCode], KeyCode),
+    /// Release everything held by the macro
+    ReleaseAll,
+    /// Wait for some number of milliseconds
+    Wait(u32),
+    /// Type out some text using keycodes for the host's layout
+    Text(&'static str),
+    /// Type out some text using the unicode input method of the host
+    Unicode(&'static str),
+}
+
+static MACROS: Channel<ThreadModeRawMutex, &'static [MacroStep], 4> = Channel::new();
+
+pub async fn run_macro(steps: &'static [MacroStep]) {
+    MACROS.send(steps).await;
+}
+
+// we only need to track keys held across steps
+struct MacroRunner {
+    held: heapless::Vec<KeyCode, 16>,
+}
+
+impl MacroRunner {
+    fn new() -> Self {
+        Self {
+            held: heapless::Vec::new(),
+        }
+    }
+
+    async fn report(&self, extra: impl IntoIterator<Item = KeyCode>) {
+        press_synthetic_keycodes(self.held.iter().copied().chain(extra)).await;
+    }
+
+    async fn tap(&self, keys: impl IntoIterator<Item = KeyCode>) {
+        self.report(keys).await;
+        self.report([]).await;
+    }
+
+    async fn run(&mut self, steps: &[MacroStep]) {
+        for step in steps {
+            match *step {
+                MacroStep::Press(k) => {
+                    if !self.held.contains(&k) && self.held.push(k).is_ok() {
+                        self.report([]).await;
+                    }
+                }
+                MacroStep::Release(k) => {
+                    if let Some(idx) = self.held.iter().position(|&h| h == k) {
+                        self.held.swap_remove(idx);
+                        self.report([]).await;
+                    }
+                }
+                MacroStep::Tap(k) => {
+                    self.tap([k]).await;
+                }
+                MacroStep::TapWith(mods, k) => {
+                    self.tap(mods.iter().copied().chain([k])).await;
+                }
+                MacroStep::ReleaseAll => {
+                    self.held.clear();
+                    self.report([]).await;
+                }
+                MacroStep::Wait(ms) => {
+                    Timer::after_millis(ms as u64).await;
+                }
+                MacroStep::Text(s) => {
+                    text::type_text(s).await;
+                    if !self.held.is_empty() {
+                        // text is typed with only its own keys pressed
+                        self.report([]).await;
+                    }
+                }
+                MacroStep::Unicode(s) => {
+                    unicode::type_unicode(s).await;
+                    if !self.held.is_empty() {
+                        // the unicode emitters leave nothing pressed
+                        self.report([]).await;
+                    }
+                }
+            }
+        }
+
+        if !self.held.is_empty() {
+            self.held.clear();
+            self.report([]).await;
+        }
+    }
+}
+
+/// Runs macros in their own task so long waits don't stall key processing
+#[embassy_executor::task]
+pub async fn macro_task() {
+    let mut runner = MacroRunner::new();
+
+    loop {
+        let steps = MACROS.receive().await;
+
+        runner.run(steps).await;
+    }
+}
@@ -1,39 +1,86 @@
 use embassy_executor::Spawner;
-use embassy_futures::select::select;
+use embassy_futures::select::{select, select3, Either3};
 use embassy_nrf::gpio::{Input, Output};
 use embassy_sync::{
     blocking_mutex::raw::ThreadModeRawMutex, channel::Channel, pubsub::PubSubChannel,
 };
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 use keyberon::{key_code::KeyCode, layout::Event};
 use packed_struct::PrimitiveEnum;
-use usbd_human_interface_device::device::keyboard::NKROBootKeyboardReport;
+use usbd_human_interface_device::{device::keyboard::NKROBootKeyboardReport, page::Keyboard};
 
 use crate::{
     ble::hid::publish_keyboard_report,
     interboard::{self},
     messages::device_to_device::DeviceToDevice,
-    side,
+    rgb, side,
+    sync::Watch,
     utils::Ticker,
 };
 
-use self::{chord::ChordingEngine, layout::LAYERS};
+use self::chord::{Chorder, ChordingEngine};
 
-#[derive(Clone, Copy)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
 pub enum UnicodeMode {
+    /// Ctrl+Shift+U followed by the hex codepoint
     Linux,
+    /// Option held while typing UTF-16 hex, needs the "Unicode Hex Input" source
     Mac,
+    /// Compose, U, the hex codepoint, then Enter
+    WinCompose,
+    /// Alt held while typing numpad plus and the hex codepoint, needs
+    /// `EnableHexNumpad` set in the registry
+    WinHexNumpad,
+    /// `C-x 8 RET` followed by the hex codepoint
+    Emacs,
 }
 
 #[derive(Clone, Copy)]
 pub enum CustomEvent {
     TypeUnicode(&'static str),
+    Macro(&'static [macros::MacroStep]),
+    CapsWord,
+    NumWord,
+    ToggleAutoShift,
+    /// Apply a modifier to the next key press, see [`oneshot`]
+    OneShotMod(KeyCode),
+    /// Activate a layer for the next key press, see [`oneshot`]
+    OneShotLayer(usize),
+    /// Cycle the unicode input mode used for the current host
+    CycleUnicodeMode,
+    /// Type out a string using the host's keyboard layout
+    TypeString(&'static str),
+    /// Cycle the keyboard layout we assume the current host is using
+    CycleHostLayout,
+    /// Turn snippet expansion on or off
+    ToggleSnippets,
+    /// Show an animation, see [`crate::rgb::AnimationControl`]
+    SetAnimation(shared::rgb::AnimationChoice),
+    /// Move on to the next animation
+    NextAnimation,
+    BrightnessUp,
+    BrightnessDown,
+    /// Turn the LEDs off, or back on at their previous brightness
+    ToggleBrightness,
 }
 
+pub mod autoshift;
 pub mod chord;
+pub mod host_text;
+pub mod indicators;
+pub mod key_colours;
 pub mod layout;
+pub mod macros;
+pub mod oneshot;
+pub mod overrides;
+mod processor;
 pub mod scan;
+pub mod snippets;
+pub mod switches;
+mod text;
 mod unicode;
+pub mod word;
 
 /// Raw matrix presses and releases
 pub static MATRIX_EVENTS: Channel<ThreadModeRawMutex, keyberon::layout::Event, 4> = Channel::new();
@@ -42,53 +89,75 @@ pub static MATRIX_EVENTS: Channel<ThreadModeRawMutex, keyberon::layout::Event, 4
 pub static AUX_MATRIX_EVENTS: PubSubChannel<ThreadModeRawMutex, keyberon::layout::Event, 4, 4, 1> =
     PubSubChannel::new();
 
+/// Layers in the keymap
+pub const NUM_LAYERS: usize = 3;
+
+/// The active layer, worked out on the master side and mirrored to the other
+pub static CURRENT_LAYER: Watch<u8> = Watch::new(0);
+
 /// Chord-processed events
 pub static KEY_EVENTS: PubSubChannel<ThreadModeRawMutex, keyberon::layout::Event, 4, 4, 2> =
     PubSubChannel::new();
 
 pub type ScannerInstance<'a> = scan::Scanner<
-    (
-        Output<'a>,
-        Output<'a>,
-        Output<'a>,
-        Output<'a>,
-        Output<'a>,
-        Output<'a>,
-        Output<'a>,
-    ),
-    (
-        Input<'a>,
-        Input<'a>,
-        Input<'a>,
-        Input<'a>,
-        Input<'a>,
-        Input<'a>,
-    ),
+    scan::ArrayMatrix<Output<'a>, Input<'a>, { switches::RAW_COLS }, { switches::RAW_ROWS }>,
+    { switches::RAW_COLS },
+    { switches::RAW_ROWS },
 >;
 
+/// How long to let a column settle after driving it before reading the rows
+pub const MATRIX_SETTLE_NS: u32 = 15_000;
+
+/// Stop scanning and wait for a key press after this long without any activity
+const SCAN_IDLE_TIMEOUT: Duration = Duration::from_secs(2);
+
 #[embassy_executor::task]
 async fn matrix_scanner(mut scanner: ScannerInstance<'static>) {
-    // TODO: pause when no activity
-
     let matrix_events = MATRIX_EVENTS.sender();
     let aux_matrix_events = AUX_MATRIX_EVENTS.publisher().unwrap();
 
+    scan::load_debounce().await;
+    let mut debounce = scan::DEBOUNCE_CONFIG.current();
+    scanner.set_debounce(debounce);
+
+    let mut last_activity = Instant::now();
+
     loop {
+        let wanted_debounce = scan::DEBOUNCE_CONFIG.current();
+        if wanted_debounce != debounce {
+            debounce = wanted_debounce;
+            scanner.set_debounce(debounce);
+        }
+
+        let mut active = false;
+
         for (evt, raw) in scanner.scan() {
+            active = true;
             matrix_events.send(evt).await;
             aux_matrix_events.publish_immediate(raw);
         }
 
+        if active || scanner.any_pressed() {
+            last_activity = Instant::now();
+        } else if last_activity.elapsed() > SCAN_IDLE_TIMEOUT {
+            crate::log::debug!("Matrix idle, waiting for a key press");
+            scanner.wait_for_press().await;
+            last_activity = Instant::now();
+
+            // scan straight away so we don't miss a short tap
+            continue;
+        }
+
         // use a timer instead of a ticker here, prevents getting stuck if matrix_events freezes
         Timer::after(Duration::from_hz(2000)).await;
     }
 }
 
 #[embassy_executor::task]
-async fn matrix_processor() {
+async fn matrix_processor(chorder: Chorder) {
     let sub = MATRIX_EVENTS.receiver();
     let key_events = KEY_EVENTS.publisher().unwrap();
-    let mut chorder = ChordingEngine::new(layout::chorder());
+    let mut chorder = ChordingEngine::new(chorder);
     let mut ticker = Ticker::every(Duration::from_hz(1000));
 
     loop {
@@ -96,6 +165,9 @@ async fn matrix_processor() {
             embassy_futures::select::Either::Second(evt) => {
                 //key_events.publish(evt).await;
                 let evts = chorder.process(evt);
+                if let Some(idx) = chorder.take_triggered() {
+                    crate::metrics::chord_triggered(idx);
+                }
                 for evt in evts {
                     embassy_futures::join::join(key_events.publish(evt), send_to_other_side(evt))
                         .await;
@@ -132,6 +204,18 @@ async fn receive_events_from_other_side() {
         let evt = match sub.next_message_pure().await {
             DeviceToDevice::KeyPress(x, y) => Event::Press(x, y),
             DeviceToDevice::KeyRelease(x, y) => Event::Release(x, y),
+            DeviceToDevice::OneShotState(s) => {
+                oneshot::ONE_SHOT_STATE.set(s);
+                continue;
+            }
+            DeviceToDevice::CurrentLayer(layer) => {
+                CURRENT_LAYER.set(layer);
+                continue;
+            }
+            DeviceToDevice::Indicators(i) => {
+                indicators::INDICATORS.set(i);
+                continue;
+            }
             _ => {
                 continue;
             }
@@ -142,56 +226,217 @@ async fn receive_events_from_other_side() {
 }
 
 #[embassy_executor::task]
-async fn key_event_processor() {
+async fn key_event_processor(chord_keys: &'static phf::Map<[u8; 2], &'static [usize]>) {
     let mut sub = KEY_EVENTS.subscriber().unwrap();
-    let mut layout = keyberon::layout::Layout::new(&LAYERS);
+    let mut processor = processor::Processor::new(chord_keys);
     let mut state = heapless::Vec::<KeyCode, 24>::new();
+    let mut one_shot_state = oneshot::OneShotState::new();
+    let mut synthetic = heapless::Vec::<Keyboard, MAX_SYNTHETIC_KEYS>::new();
+    let mut indicators = indicators::Indicators::new();
     let mut ticker = Ticker::every(Duration::from_hz(1000));
 
     loop {
-        match select(ticker.next(), sub.next_message_pure()).await {
-            embassy_futures::select::Either::Second(evt) => {
+        let mut synthetic_changed = false;
+
+        match select3(
+            ticker.next(),
+            sub.next_message_pure(),
+            SYNTHETIC_KEYS.receive(),
+        )
+        .await
+        {
+            Either3::Second(evt) => {
                 // crate::utils::log::info!("evt: {:?}", evt);
 
-                layout.event(evt);
+                processor.event(evt);
             }
-            embassy_futures::select::Either::First(_) => {
-                let cevent = layout.tick();
-                if let Some((evt, is_press)) = match cevent {
-                    keyberon::layout::CustomEvent::NoEvent => None,
-                    keyberon::layout::CustomEvent::Press(m) => Some((*m, true)),
-                    keyberon::layout::CustomEvent::Release(m) => Some((*m, false)),
-                } {
+            Either3::Third(keys) => {
+                synthetic = keys;
+                synthetic_changed = true;
+            }
+            Either3::First(_) => {
+                if let Some((evt, is_press)) = processor.tick() {
                     match evt {
                         CustomEvent::TypeUnicode(msg) => {
                             if !is_press {
                                 unicode::send_unicode(msg).await;
                             }
                         }
+                        CustomEvent::Macro(steps) => {
+                            if is_press {
+                                macros::run_macro(steps).await;
+                            }
+                        }
+                        CustomEvent::CapsWord => {
+                            if is_press {
+                                processor.toggle_caps_word();
+                            }
+                        }
+                        CustomEvent::NumWord => {
+                            if is_press {
+                                processor.toggle_num_word();
+                            }
+                        }
+                        CustomEvent::ToggleAutoShift => {
+                            if is_press {
+                                processor.toggle_auto_shift();
+                            }
+                        }
+                        CustomEvent::OneShotMod(k) => {
+                            if is_press {
+                                processor.one_shot_mod(k);
+                            }
+                        }
+                        CustomEvent::OneShotLayer(l) => {
+                            if is_press {
+                                processor.one_shot_layer(l);
+                            }
+                        }
+                        CustomEvent::CycleUnicodeMode => {
+                            if is_press {
+                                unicode::cycle_unicode_mode().await;
+                            }
+                        }
+                        CustomEvent::TypeString(s) => {
+                            if !is_press {
+                                text::send_text(s).await;
+                            }
+                        }
+                        CustomEvent::CycleHostLayout => {
+                            if is_press {
+                                text::cycle_host_layout().await;
+                            }
+                        }
+                        CustomEvent::ToggleSnippets => {
+                            if is_press {
+                                snippets::send_command(snippets::SnippetCommand::ToggleEnabled)
+                                    .await;
+                            }
+                        }
+                        CustomEvent::SetAnimation(choice) => {
+                            if is_press {
+                                rgb::control_animation(rgb::AnimationControl::Set(choice)).await;
+                            }
+                        }
+                        CustomEvent::NextAnimation => {
+                            if is_press {
+                                rgb::control_animation(rgb::AnimationControl::Next).await;
+                            }
+                        }
+                        CustomEvent::BrightnessUp => {
+                            if is_press {
+                                rgb::brightness::up().await;
+                            }
+                        }
+                        CustomEvent::BrightnessDown => {
+                            if is_press {
+                                rgb::brightness::down().await;
+                            }
+                        }
+                        CustomEvent::ToggleBrightness => {
+                            if is_press {
+                                rgb::brightness::toggle().await;
+                            }
+                        }
                     }
                 }
             }
         }
 
-        let new_state = heapless::Vec::<_, 24>::from_iter(layout.keycodes());
+        let new_state = processor.keycodes();
 
-        if new_state != state {
+        let state_changed = new_state != state;
+
+        if state_changed {
+            snippets::report_changed(&state, &new_state);
             state = new_state;
+        }
 
-            publish_keyboard_report(NKROBootKeyboardReport::new(state.iter().filter_map(|k| {
-                usbd_human_interface_device::page::Keyboard::from_primitive(*k as u8)
-            })))
+        if state_changed || synthetic_changed {
+            let keys = state.iter().copied().filter_map(to_keyboard);
+            publish_keyboard_report(NKROBootKeyboardReport::new(
+                keys.chain(synthetic.iter().copied()),
+            ))
             .await;
         }
+
+        let layer = processor.current_layer() as u8;
+
+        if layer != CURRENT_LAYER.current() {
+            CURRENT_LAYER.set(layer);
+            interboard::send_msg(DeviceToDevice::CurrentLayer(layer), 3).await;
+        }
+
+        let new_one_shot_state = processor.one_shot_state();
+
+        if new_one_shot_state != one_shot_state {
+            one_shot_state = new_one_shot_state;
+
+            oneshot::ONE_SHOT_STATE.set(one_shot_state);
+            interboard::send_msg(DeviceToDevice::OneShotState(one_shot_state), 3).await;
+        }
+
+        let new_indicators = indicators::Indicators {
+            held_mods: indicators::held_mods(&state),
+            caps_word: processor.caps_word(),
+            caps_lock: crate::state::current_host_leds() & crate::state::CAPS_LOCK_LED != 0,
+            ble_host: crate::state::BLE_HID_HOST.current(),
+        };
+
+        if new_indicators != indicators {
+            indicators = new_indicators;
+
+            indicators::INDICATORS.set(indicators);
+            interboard::send_msg(DeviceToDevice::Indicators(indicators), 3).await;
+        }
+    }
+}
+
+fn to_keyboard(k: KeyCode) -> Option<Keyboard> {
+    Keyboard::from_primitive(k as u8)
+}
+
+/// Most keys that can be pressed by [`press_synthetic`] at once
+const MAX_SYNTHETIC_KEYS: usize = 24;
+
+/// Keys pressed by macros and typed text, the key event processor adds them to
+/// the keys from the layout so they don't fight over the report sent to the
+/// host
+static SYNTHETIC_KEYS: Channel<ThreadModeRawMutex, heapless::Vec<Keyboard, MAX_SYNTHETIC_KEYS>, 1> =
+    Channel::new();
+
+/// Press `keys` on top of the keys held by the user, replacing whatever was
+/// last pressed this way
+pub(crate) async fn press_synthetic(keys: impl IntoIterator<Item = Keyboard>) {
+    let mut pressed = heapless::Vec::new();
+
+    for k in keys {
+        if pressed.push(k).is_err() {
+            crate::log::warn!("Too many keys pressed at once, dropping some");
+            break;
+        }
     }
+
+    SYNTHETIC_KEYS.send(pressed).await;
+}
+
+pub(crate) async fn press_synthetic_keycodes(keys: impl IntoIterator<Item = KeyCode>) {
+    press_synthetic(keys.into_iter().filter_map(to_keyboard)).await;
 }
 
 pub fn init(spawner: &Spawner, scanner: ScannerInstance<'static>) {
-    spawner.must_spawn(matrix_processor());
+    let chorder = layout::chorder();
+    let chord_keys = chorder.key_chord_map;
+
+    spawner.must_spawn(matrix_processor(chorder));
     spawner.must_spawn(matrix_scanner(scanner));
     spawner.must_spawn(receive_events_from_other_side());
     if side::is_master() {
-        spawner.must_spawn(key_event_processor());
+        spawner.must_spawn(key_event_processor(chord_keys));
         spawner.must_spawn(unicode::unicode_task());
+        spawner.must_spawn(text::text_task());
+        spawner.must_spawn(host_text::host_text_task());
+        spawner.must_spawn(snippets::snippet_task());
+        spawner.must_spawn(macros::macro_task());
     }
 }
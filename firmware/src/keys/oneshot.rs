@@ -0,0 +1,213 @@
+use embassy_time::{Duration, Instant};
+use keyberon::{action::Action, key_code::KeyCode, layout::Event};
+use serde::{Deserialize, Serialize};
+
+use crate::sync::Watch;
+
+use super::{chord::Key, layout::LAYERS, CustomEvent};
+
+/// Pending one-shots are dropped if no key is pressed within this time
+pub const ONE_SHOT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The current one-shot state, kept in sync on both sides so the LEDs can show it
+pub static ONE_SHOT_STATE: Watch<OneShotState> = Watch::new(OneShotState::new());
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub struct OneShotState {
+    /// Modifiers (as HID modifier bits) applied to the next key press
+    pub pending_mods: u8,
+    /// Modifiers locked on by tapping their one-shot key twice
+    pub locked_mods: u8,
+    pub pending_layer: Option<u8>,
+    pub locked_layer: Option<u8>,
+}
+
+impl Default for OneShotState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OneShotState {
+    pub const fn new() -> Self {
+        Self {
+            pending_mods: 0,
+            locked_mods: 0,
+            pending_layer: None,
+            locked_layer: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        *self != Self::new()
+    }
+}
+
+pub(super) fn modifier_bit(k: KeyCode) -> u8 {
+    if (KeyCode::LCtrl as u8..=KeyCode::RGui as u8).contains(&(k as u8)) {
+        1 << (k as u8 - KeyCode::LCtrl as u8)
+    } else {
+        0
+    }
+}
+
+const MODIFIERS: [KeyCode; 8] = [
+    KeyCode::LCtrl,
+    KeyCode::LShift,
+    KeyCode::LAlt,
+    KeyCode::LGui,
+    KeyCode::RCtrl,
+    KeyCode::RShift,
+    KeyCode::RAlt,
+    KeyCode::RGui,
+];
+
+/// One-shot modifiers and layers
+///
+/// Tapping a one-shot key applies it to the next key press, tapping it again
+/// while pending locks it on until it is tapped a third time. Escape clears
+/// everything.
+pub struct OneShot {
+    state: OneShotState,
+    activated_at: Instant,
+    /// The key that consumed the pending one-shots, they stay applied until
+    /// it is released
+    consumer: Option<Key>,
+    last_keycodes: heapless::Vec<KeyCode, 24>,
+}
+
+impl Default for OneShot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OneShot {
+    pub fn new() -> Self {
+        Self {
+            state: OneShotState::new(),
+            activated_at: Instant::now(),
+            consumer: None,
+            last_keycodes: heapless::Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> OneShotState {
+        self.state
+    }
+
+    pub fn mod_tapped(&mut self, k: KeyCode) {
+        let bit = modifier_bit(k);
+
+        if self.state.locked_mods & bit != 0 {
+            self.state.locked_mods &= !bit;
+        } else if self.state.pending_mods & bit != 0 {
+            self.state.pending_mods &= !bit;
+            self.state.locked_mods |= bit;
+        } else {
+            self.state.pending_mods |= bit;
+            self.activated_at = Instant::now();
+        }
+    }
+
+    pub fn layer_tapped(&mut self, layer: usize) {
+        let layer = Some(layer as u8);
+
+        if self.state.locked_layer == layer {
+            self.state.locked_layer = None;
+        } else if self.state.pending_layer == layer {
+            self.state.pending_layer = None;
+            self.state.locked_layer = layer;
+        } else {
+            self.state.pending_layer = layer;
+            self.activated_at = Instant::now();
+        }
+    }
+
+    /// The layer that should be the default layer, if any
+    pub fn layer(&self) -> Option<usize> {
+        self.state
+            .pending_layer
+            .or(self.state.locked_layer)
+            .map(|l| l as usize)
+    }
+
+    fn has_pending(&self) -> bool {
+        self.state.pending_mods != 0 || self.state.pending_layer.is_some()
+    }
+
+    /// Called before an event is passed to the layout
+    pub fn event(&mut self, evt: Event, layer: usize) {
+        match evt {
+            Event::Press(x, y) => {
+                if self.consumer.is_some() || !self.has_pending() {
+                    return;
+                }
+
+                // modifiers and other one-shot keys don't use up the one-shot
+                let consumes = match LAYERS[layer][x as usize][y as usize] {
+                    Action::KeyCode(k) => modifier_bit(k) == 0,
+                    Action::Custom(CustomEvent::OneShotMod(_) | CustomEvent::OneShotLayer(_)) => {
+                        false
+                    }
+                    _ => true,
+                };
+
+                if consumes {
+                    self.consumer = Some((x, y));
+                }
+            }
+            Event::Release(x, y) => {
+                if self.consumer == Some((x, y)) {
+                    self.consumer = None;
+                    self.state.pending_mods = 0;
+                    self.state.pending_layer = None;
+                }
+            }
+        }
+    }
+
+    /// Apply one-shot modifiers to the keycodes about to be sent
+    pub fn process(&mut self, keycodes: &mut heapless::Vec<KeyCode, 24>) {
+        let escape_pressed =
+            keycodes.contains(&KeyCode::Escape) && !self.last_keycodes.contains(&KeyCode::Escape);
+        self.last_keycodes = keycodes.clone();
+
+        if escape_pressed {
+            self.state = OneShotState::new();
+            self.consumer = None;
+        }
+
+        if self.consumer.is_none()
+            && self.has_pending()
+            && self.activated_at.elapsed() > ONE_SHOT_TIMEOUT
+        {
+            self.state.pending_mods = 0;
+            self.state.pending_layer = None;
+        }
+
+        let mods = self.state.locked_mods
+            | if self.consumer.is_some() {
+                self.state.pending_mods
+            } else {
+                0
+            };
+
+        for (i, k) in MODIFIERS.iter().enumerate() {
+            if mods & (1 << i) != 0 && !keycodes.contains(k) {
+                let _ = keycodes.push(*k);
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use keyberon::key_code::KeyCode;
+
+/// Modifier masks, the left and right variants of a modifier are treated the
+/// same
+pub mod mods {
+    pub const CTRL: u8 = 1 << 0;
+    pub const SHIFT: u8 = 1 << 1;
+    pub const ALT: u8 = 1 << 2;
+    pub const GUI: u8 = 1 << 3;
+}
+
+pub const ALL_LAYERS: u16 = u16::MAX;
+
+/// Replaces `trigger` with `replacement` while the modifiers in `mods` are held
+pub struct KeyOverride {
+    pub trigger: KeyCode,
+    pub mods: u8,
+    pub replacement: &'static [KeyCode],
+    /// Bitmask of layers the override applies on
+    pub layers: u16,
+    /// The override doesn't apply if any of these modifiers are held
+    pub negative_mods: u8,
+}
+
+pub static OVERRIDES: &[KeyOverride] = &[
+    // shift + backspace = delete
+    KeyOverride {
+        trigger: KeyCode::BSpace,
+        mods: mods::SHIFT,
+        replacement: &[KeyCode::Delete],
+        layers: ALL_LAYERS,
+        negative_mods: 0,
+    },
+];
+
+fn modifier_mask(k: KeyCode) -> u8 {
+    match k {
+        KeyCode::LCtrl | KeyCode::RCtrl => mods::CTRL,
+        KeyCode::LShift | KeyCode::RShift => mods::SHIFT,
+        KeyCode::LAlt | KeyCode::RAlt => mods::ALT,
+        KeyCode::LGui | KeyCode::RGui => mods::GUI,
+        _ => 0,
+    }
+}
+
+/// Apply the first matching override (if any) to the keycodes about to be sent
+pub fn apply(overrides: &[KeyOverride], layer: usize, keycodes: &mut heapless::Vec<KeyCode, 24>) {
+    let held = keycodes.iter().fold(0u8, |acc, &k| acc | modifier_mask(k));
+
+    let layer_bit = 1u16.checked_shl(layer as u32).unwrap_or(0);
+
+    let Some(o) = overrides.iter().find(|o| {
+        o.layers & layer_bit != 0
+            && held & o.mods == o.mods
+            && held & o.negative_mods == 0
+            && keycodes.contains(&o.trigger)
+    }) else {
+        return;
+    };
+
+    // the triggering modifiers are suppressed, other held modifiers stay
+    keycodes.retain(|&k| k != o.trigger && modifier_mask(k) & o.mods == 0);
+
+    for &k in o.replacement {
+        if !keycodes.contains(&k) {
+            let _ = keycodes.push(k);
+        }
+    }
+}
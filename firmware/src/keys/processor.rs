@@ -0,0 +1,117 @@
+use keyberon::{key_code::KeyCode, layout::Event};
+
+use super::{
+    autoshift::{self, AutoShift},
+    layout::LAYERS,
+    oneshot::{OneShot, OneShotState},
+    overrides,
+    switches::{LAYOUT_COLS, LAYOUT_ROWS},
+    word::WordModes,
+    CustomEvent, NUM_LAYERS,
+};
+
+type Layout = keyberon::layout::Layout<LAYOUT_COLS, LAYOUT_ROWS, NUM_LAYERS, CustomEvent>;
+
+/// The layout along with the stages that sit around it
+///
+/// Events go through auto shift, one-shot and word tracking before reaching
+/// keyberon, and the keycodes keyberon produces go through word modes,
+/// one-shots, auto shift and key overrides before becoming a report.
+pub struct Processor {
+    layout: Layout,
+    words: WordModes,
+    autoshift: AutoShift,
+    oneshot: OneShot,
+    default_layer: usize,
+}
+
+impl Processor {
+    pub fn new(chord_keys: &'static phf::Map<[u8; 2], &'static [usize]>) -> Self {
+        Self {
+            layout: Layout::new(&LAYERS),
+            words: WordModes::new(),
+            autoshift: AutoShift::new(chord_keys),
+            oneshot: OneShot::new(),
+            default_layer: 0,
+        }
+    }
+
+    pub fn event(&mut self, evt: Event) {
+        let evts = self.autoshift.event(evt, self.layout.current_layer());
+        self.feed(evts);
+    }
+
+    fn feed(&mut self, evts: autoshift::Events) {
+        for evt in evts {
+            self.oneshot.event(evt, self.layout.current_layer());
+            self.words.event(evt);
+            self.layout.event(evt);
+        }
+    }
+
+    /// Tick the layout, returning any custom event that occurred and whether
+    /// it was a press
+    pub fn tick(&mut self) -> Option<(CustomEvent, bool)> {
+        let evts = self.autoshift.tick();
+        self.feed(evts);
+
+        match self.layout.tick() {
+            keyberon::layout::CustomEvent::NoEvent => None,
+            keyberon::layout::CustomEvent::Press(m) => Some((*m, true)),
+            keyberon::layout::CustomEvent::Release(m) => Some((*m, false)),
+        }
+    }
+
+    pub fn toggle_caps_word(&mut self) {
+        self.words.toggle_caps_word();
+    }
+
+    pub fn caps_word(&self) -> bool {
+        self.words.caps_word()
+    }
+
+    pub fn toggle_num_word(&mut self) {
+        self.words.toggle_num_word();
+    }
+
+    pub fn toggle_auto_shift(&mut self) {
+        let evts = self.autoshift.toggle();
+        self.feed(evts);
+    }
+
+    pub fn one_shot_mod(&mut self, k: KeyCode) {
+        self.oneshot.mod_tapped(k);
+    }
+
+    pub fn one_shot_layer(&mut self, layer: usize) {
+        self.oneshot.layer_tapped(layer);
+    }
+
+    pub fn one_shot_state(&self) -> OneShotState {
+        self.oneshot.state()
+    }
+
+    pub fn current_layer(&self) -> usize {
+        self.layout.current_layer()
+    }
+
+    pub fn keycodes(&mut self) -> heapless::Vec<KeyCode, 24> {
+        let wanted_layer = self.oneshot.layer().or(self.words.layer()).unwrap_or(0);
+        if wanted_layer != self.default_layer {
+            self.default_layer = wanted_layer;
+            self.layout.set_default_layer(wanted_layer);
+        }
+
+        let mut keycodes = heapless::Vec::from_iter(self.layout.keycodes());
+        self.words.process(&mut keycodes);
+        self.oneshot.process(&mut keycodes);
+        self.autoshift.process(&mut keycodes);
+        overrides::apply(
+            overrides::OVERRIDES,
+            self.layout.current_layer(),
+            &mut keycodes,
+        );
+
+        keycodes
+    }
+}
@@ -1,214 +1,250 @@
 use core::convert::Infallible;
 
+use embassy_futures::select::select_array;
+use embassy_time::Instant;
 use embedded_hal_0_2::digital::v2::{InputPin, OutputPin};
+use embedded_hal_async::digital::Wait;
+use shared::debounce::{Debounce, DebounceConfig, Debouncer};
 
-const THUMB_CLUSTER_PATCH_R: [(u8, u8); 6] = [(8, 6), (7, 6), (6, 6), (8, 7), (7, 7), (6, 7)];
-const THUMB_CLUSTER_PATCH_L: [(u8, u8); 6] = [(3, 6), (4, 6), (5, 6), (3, 7), (4, 7), (5, 7)];
-
-// patch the thumb cluster keys to end up on two rows beneath the main keys
-//
-// doing this because I'm too lazy to update my keymap generator
-fn patch_pos(x: u8, y: u8) -> (u8, u8) {
-    if crate::side::get_side().is_right() {
-        if x == 0 {
-            THUMB_CLUSTER_PATCH_R[y as usize]
-        } else {
-            (x + 5, y)
-        }
-    } else if x == 6 {
-        THUMB_CLUSTER_PATCH_L[y as usize]
-    } else {
-        (x, y)
+use crate::sync::Watch;
+
+/// The debounce algorithm used by the scanner on this side
+pub static DEBOUNCE_CONFIG: Watch<DebounceConfig> = Watch::new(DebounceConfig::DEFAULT);
+
+/// Load the saved debounce config, if any
+pub async fn load_debounce() {
+    if let Some(config) = crate::flash::get::<DebounceConfig>().await {
+        crate::log::info!("Loaded debounce config: {}", config);
+        DEBOUNCE_CONFIG.set(config);
     }
 }
 
-pub struct Scanner<C, R>
-where
-    <C as ScanMatrix<R>>::Debouncers: Default,
-    C: ScanMatrix<R>,
-    R: ScanRows,
-{
-    cols: C,
-    rows: R,
-    debouncers: <C as ScanMatrix<R>>::Debouncers,
+pub async fn set_debounce(config: DebounceConfig) {
+    crate::log::info!("Setting debounce config: {}", config);
+    DEBOUNCE_CONFIG.set(config);
+    crate::flash::set(&config).await;
 }
 
-impl<C, R> Scanner<C, R>
-where
-    <C as ScanMatrix<R>>::Debouncers: Default,
-    C: ScanMatrix<R>,
-    R: ScanRows,
-{
-    pub fn new(cols: C, rows: R) -> Self {
-        Self {
-            cols,
-            rows,
-            debouncers: Default::default(),
-        }
-    }
-
-    /// (translated, raw)
-    pub fn scan(
-        &mut self,
-    ) -> impl Iterator<Item = (keyberon::layout::Event, keyberon::layout::Event)> {
-        let scan_result = self.cols.scan_matrix(&self.rows, &mut self.debouncers);
-
-        scan_result.into_iter().enumerate().flat_map(|(j, row)| {
-            row.into_iter()
-                .enumerate()
-                .filter_map(move |(i, press_state)| {
-                    press_state.map(|press| {
-                        let (i, j) = (i as u8, j as u8);
-                        let (x, y) = patch_pos(j, i);
-                        if press {
-                            crate::log::debug!("kp: ({}, {}) (orig: ({}, {}))", x, y, j, i);
-                            (
-                                keyberon::layout::Event::Press(y, x),
-                                keyberon::layout::Event::Press(j, i),
-                            )
-                        } else {
-                            (
-                                keyberon::layout::Event::Release(y, x),
-                                keyberon::layout::Event::Release(j, i),
-                            )
-                        }
-                    })
-                })
-        })
-    }
+/// Which way the diodes point, this decides which pins we drive and which we read
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiodeDirection {
+    /// Columns are driven high and rows read
+    Col2Row,
+    /// Rows are driven high and columns read
+    Row2Col,
 }
 
-pub trait ScanRows {
-    type Result: IntoIterator<Item = Option<bool>>;
-    type Debouncers;
+/// The CPU runs at 64MHz, used to turn settle times into busy-wait cycles
+const CYCLES_PER_US: u32 = 64;
 
-    fn scan_rows(&self, debouncers: &mut Self::Debouncers) -> Self::Result;
+fn settle(ns: u32) {
+    cortex_m::asm::delay(ns.saturating_mul(CYCLES_PER_US) / 1000);
 }
 
-const DEBOUNCE_PERIOD: u8 = 40;
+/// Something we can read the raw state of every switch from
+///
+/// Readings are indexed by the pin that was driven (`OUTS`) and then by the pin
+/// that was read (`INS`).
+pub trait Matrix<const OUTS: usize, const INS: usize> {
+    fn read(&mut self) -> [[bool; INS]; OUTS];
 
-impl<C0, C1, C2, C3, C4, C5> ScanRows for (C0, C1, C2, C3, C4, C5)
-where
-    C0: InputPin<Error = Infallible>,
-    C1: InputPin<Error = Infallible>,
-    C2: InputPin<Error = Infallible>,
-    C3: InputPin<Error = Infallible>,
-    C4: InputPin<Error = Infallible>,
-    C5: InputPin<Error = Infallible>,
-{
-    type Result = [Option<bool>; 6];
-    type Debouncers = [Debouncer<DEBOUNCE_PERIOD>; 6];
-
-    fn scan_rows(&self, debouncers: &mut Self::Debouncers) -> Self::Result {
-        cortex_m::asm::delay(1000);
-        [
-            debouncers[0].update(self.0.is_high().unwrap()),
-            debouncers[1].update(self.1.is_high().unwrap()),
-            debouncers[2].update(self.2.is_high().unwrap()),
-            debouncers[3].update(self.3.is_high().unwrap()),
-            debouncers[4].update(self.4.is_high().unwrap()),
-            debouncers[5].update(self.5.is_high().unwrap()),
-        ]
-    }
+    /// The raw (column, row) of the switch at a reading index
+    fn position(&self, output: usize, input: usize) -> (u8, u8);
+
+    /// Wait until any switch reads pressed, the switch is still held when this
+    /// returns so the next read picks it up
+    async fn wait_for_press(&mut self);
 }
 
-pub trait ScanMatrix<C: ScanRows> {
-    type Result: IntoIterator<Item = C::Result>;
-    type Debouncers;
+/// A diode matrix with one output pin per driven line and one input pin per
+/// read line
+///
+/// Outputs are driven high one at a time, inputs should be pulled down.
+pub struct ArrayMatrix<O, I, const OUTS: usize, const INS: usize> {
+    outputs: [O; OUTS],
+    inputs: [I; INS],
+    direction: DiodeDirection,
+    settle_ns: u32,
+}
 
-    fn scan_matrix(&mut self, columns: &C, debouncers: &mut Self::Debouncers) -> Self::Result;
+impl<O, I, const OUTS: usize, const INS: usize> ArrayMatrix<O, I, OUTS, INS> {
+    /// `outputs` are the columns for [`DiodeDirection::Col2Row`] and the rows for
+    /// [`DiodeDirection::Row2Col`], `settle_ns` is how long to wait after driving
+    /// an output before reading the inputs
+    pub fn new(
+        outputs: [O; OUTS],
+        inputs: [I; INS],
+        direction: DiodeDirection,
+        settle_ns: u32,
+    ) -> Self {
+        Self {
+            outputs,
+            inputs,
+            direction,
+            settle_ns,
+        }
+    }
 }
 
-impl<R, C0, C1, C2, C3, C4, C5, C6> ScanMatrix<R> for (C0, C1, C2, C3, C4, C5, C6)
+impl<O, I, const OUTS: usize, const INS: usize> Matrix<OUTS, INS> for ArrayMatrix<O, I, OUTS, INS>
 where
-    R: ScanRows,
-    C0: OutputPin<Error = Infallible>,
-    C1: OutputPin<Error = Infallible>,
-    C2: OutputPin<Error = Infallible>,
-    C3: OutputPin<Error = Infallible>,
-    C4: OutputPin<Error = Infallible>,
-    C5: OutputPin<Error = Infallible>,
-    C6: OutputPin<Error = Infallible>,
+    O: OutputPin<Error = Infallible>,
+    I: InputPin<Error = Infallible> + Wait,
 {
-    type Result = [R::Result; 7];
-    type Debouncers = [R::Debouncers; 7];
+    fn read(&mut self) -> [[bool; INS]; OUTS] {
+        let mut readings = [[false; INS]; OUTS];
 
-    fn scan_matrix(&mut self, rows: &R, debouncers: &mut Self::Debouncers) -> Self::Result {
-        self.0.set_high().unwrap();
-        let a = rows.scan_rows(&mut debouncers[0]);
-        self.0.set_low().unwrap();
+        for (output, line) in self.outputs.iter_mut().zip(readings.iter_mut()) {
+            output.set_high().unwrap();
+            settle(self.settle_ns);
 
-        self.1.set_high().unwrap();
-        let b = rows.scan_rows(&mut debouncers[1]);
-        self.1.set_low().unwrap();
+            for (input, reading) in self.inputs.iter().zip(line.iter_mut()) {
+                *reading = input.is_high().unwrap();
+            }
 
-        self.2.set_high().unwrap();
-        let c = rows.scan_rows(&mut debouncers[2]);
-        self.2.set_low().unwrap();
+            output.set_low().unwrap();
+        }
 
-        self.3.set_high().unwrap();
-        let d = rows.scan_rows(&mut debouncers[3]);
-        self.3.set_low().unwrap();
+        readings
+    }
 
-        self.4.set_high().unwrap();
-        let e = rows.scan_rows(&mut debouncers[4]);
-        self.4.set_low().unwrap();
+    fn position(&self, output: usize, input: usize) -> (u8, u8) {
+        match self.direction {
+            DiodeDirection::Col2Row => (output as u8, input as u8),
+            DiodeDirection::Row2Col => (input as u8, output as u8),
+        }
+    }
 
-        self.5.set_high().unwrap();
-        let f = rows.scan_rows(&mut debouncers[5]);
-        self.5.set_low().unwrap();
+    async fn wait_for_press(&mut self) {
+        for output in &mut self.outputs {
+            output.set_high().unwrap();
+        }
 
-        self.6.set_high().unwrap();
-        let g = rows.scan_rows(&mut debouncers[6]);
-        self.6.set_low().unwrap();
+        select_array(self.inputs.each_mut().map(|i| i.wait_for_high())).await;
 
-        [a, b, c, d, e, f, g]
+        for output in &mut self.outputs {
+            output.set_low().unwrap();
+        }
     }
 }
 
-pub struct Debouncer<const MAX: u8> {
-    timer: u8,
-    is_pressed: bool,
+/// Switches wired straight to their own input pin, the raw position of each
+/// switch is (pin index, 0)
+pub struct DirectPins<I, const N: usize> {
+    pins: [I; N],
+    active_high: bool,
 }
 
-impl<const MAX: u8> Default for Debouncer<MAX> {
-    fn default() -> Self {
-        Self {
-            timer: 0,
-            is_pressed: false,
-        }
+impl<I, const N: usize> DirectPins<I, N> {
+    /// `active_high` is for switches wired to VCC with pull downs, otherwise
+    /// they're expected to be wired to ground with pull ups
+    pub fn new(pins: [I; N], active_high: bool) -> Self {
+        Self { pins, active_high }
     }
 }
 
-impl<const MAX: u8> Debouncer<MAX> {
-    fn update(&mut self, is_pressed: bool) -> Option<bool> {
-        self.timer = self.timer.saturating_sub(1);
+impl<I, const N: usize> Matrix<1, N> for DirectPins<I, N>
+where
+    I: InputPin<Error = Infallible> + Wait,
+{
+    fn read(&mut self) -> [[bool; N]; 1] {
+        [core::array::from_fn(|i| {
+            self.pins[i].is_high().unwrap() == self.active_high
+        })]
+    }
 
-        if is_pressed {
-            self.pressed()
-        } else {
-            self.unpressed()
+    fn position(&self, _output: usize, input: usize) -> (u8, u8) {
+        (input as u8, 0)
+    }
+
+    async fn wait_for_press(&mut self) {
+        let active_high = self.active_high;
+
+        select_array(self.pins.each_mut().map(|p| async move {
+            if active_high {
+                p.wait_for_high().await
+            } else {
+                p.wait_for_low().await
+            }
+        }))
+        .await;
+    }
+}
+
+pub struct Scanner<M, const OUTS: usize, const INS: usize> {
+    matrix: M,
+    debouncers: [[Debouncer; INS]; OUTS],
+}
+
+impl<M, const OUTS: usize, const INS: usize> Scanner<M, OUTS, INS>
+where
+    M: Matrix<OUTS, INS>,
+{
+    pub fn new(matrix: M) -> Self {
+        Self {
+            matrix,
+            debouncers: [[Debouncer::default(); INS]; OUTS],
         }
     }
 
-    fn unpressed(&mut self) -> Option<bool> {
-        if self.timer == 0 && self.is_pressed {
-            self.timer = MAX;
-            self.is_pressed = false;
-            return Some(false);
+    pub fn set_debounce(&mut self, config: DebounceConfig) {
+        for d in self.debouncers.iter_mut().flatten() {
+            d.reconfigure(config);
         }
+    }
 
-        None
+    /// Whether any key is held, after debouncing
+    pub fn any_pressed(&self) -> bool {
+        self.debouncers.iter().flatten().any(|d| d.is_pressed())
     }
 
-    fn pressed(&mut self) -> Option<bool> {
-        if self.timer == 0 && !self.is_pressed {
-            self.timer = MAX;
-            self.is_pressed = true;
+    /// Wait for any key to be pressed, so we can stop scanning while nothing
+    /// is happening
+    ///
+    /// The key that woke us is still held when this returns, so the next scan
+    /// picks it up.
+    pub async fn wait_for_press(&mut self) {
+        self.matrix.wait_for_press().await;
+    }
 
-            return Some(true);
-        }
+    /// (translated, raw)
+    pub fn scan(
+        &mut self,
+    ) -> impl Iterator<Item = (keyberon::layout::Event, keyberon::layout::Event)> + '_ {
+        let now = Instant::now().as_millis() as u32;
+        let readings = self.matrix.read();
+        let matrix = &self.matrix;
+        let switches = super::switches::this_side();
+
+        readings
+            .into_iter()
+            .zip(self.debouncers.iter_mut())
+            .enumerate()
+            .flat_map(move |(o, (line, debouncers))| {
+                line.into_iter()
+                    .zip(debouncers.iter_mut())
+                    .enumerate()
+                    .filter_map(move |(i, (raw, debouncer))| {
+                        let press = debouncer.update(raw, now)?;
+                        let (j, i) = matrix.position(o, i);
+                        let Some((x, y)) = switches.logical((j, i)) else {
+                            crate::log::warn!("No switch at raw position ({}, {})", j, i);
+                            return None;
+                        };
 
-        None
+                        if press {
+                            crate::log::debug!("kp: ({}, {}) (orig: ({}, {}))", x, y, j, i);
+                            Some((
+                                keyberon::layout::Event::Press(y, x),
+                                keyberon::layout::Event::Press(j, i),
+                            ))
+                        } else {
+                            Some((
+                                keyberon::layout::Event::Release(y, x),
+                                keyberon::layout::Event::Release(j, i),
+                            ))
+                        }
+                    })
+            })
     }
 }
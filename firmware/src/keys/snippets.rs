@@ -0,0 +1,355 @@
+use embassy_futures::select::{select, Either};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use keyberon::key_code::KeyCode;
+use serde::{Deserialize, Serialize};
+use shared::{
+    device_to_host::DeviceToHostMsg,
+    host_to_device::{MAX_SNIPPET_TRIGGER, MAX_TEXT_CHUNK},
+};
+
+use crate::{
+    flash,
+    messages::{distributors::MessageProvenance, send_to_host},
+    sync::Watch,
+};
+
+use super::text;
+
+pub const MAX_SNIPPETS: usize = 32;
+pub const MAX_EXPANSION: usize = 256;
+
+/// Characters that end a trigger, the terminator is typed again after the
+/// expansion
+const TERMINATORS: &[char] = &[' ', '\t'];
+
+type Trigger = heapless::String<MAX_SNIPPET_TRIGGER>;
+type Expansion = heapless::String<MAX_EXPANSION>;
+
+#[derive(Serialize, Deserialize, Default)]
+struct SavedTriggers(heapless::Vec<Trigger, MAX_SNIPPETS>);
+
+#[derive(Serialize)]
+struct SnippetKey(Trigger);
+
+#[derive(Serialize, Deserialize)]
+struct SnippetsEnabled(bool);
+
+pub enum SnippetCommand {
+    Set {
+        trigger: heapless::Vec<u8, MAX_SNIPPET_TRIGGER>,
+        seq: u8,
+        last: bool,
+        chunk: heapless::Vec<u8, MAX_TEXT_CHUNK>,
+    },
+    Delete(heapless::Vec<u8, MAX_SNIPPET_TRIGGER>),
+    SetEnabled(bool),
+    ToggleEnabled,
+    List,
+}
+
+enum Typed {
+    Key {
+        key: KeyCode,
+        shift: bool,
+        alt_gr: bool,
+    },
+    Backspace,
+    /// Something that moves the cursor or otherwise breaks up what was typed
+    Reset,
+}
+
+static TYPED: Channel<ThreadModeRawMutex, Typed, 16> = Channel::new();
+static COMMANDS: Channel<ThreadModeRawMutex, SnippetCommand, 2> = Channel::new();
+
+/// Whether any keys are held down, expansion waits for everything to be
+/// released so it doesn't mix with keys the processor is still reporting
+static KEYS_HELD: Watch<bool> = Watch::new(false);
+
+pub async fn send_command(cmd: SnippetCommand) {
+    COMMANDS.send(cmd).await;
+}
+
+fn is_modifier(k: KeyCode) -> bool {
+    (KeyCode::LCtrl as u8..=KeyCode::RGui as u8).contains(&(k as u8))
+}
+
+/// Track keys pressed between two reports sent by the key processor
+pub fn report_changed(old: &[KeyCode], new: &[KeyCode]) {
+    KEYS_HELD.set(!new.is_empty());
+
+    let shift = new.contains(&KeyCode::LShift) || new.contains(&KeyCode::RShift);
+    let alt_gr = new.contains(&KeyCode::RAlt);
+    let shortcut = [
+        KeyCode::LCtrl,
+        KeyCode::RCtrl,
+        KeyCode::LAlt,
+        KeyCode::LGui,
+        KeyCode::RGui,
+    ]
+    .iter()
+    .any(|k| new.contains(k));
+
+    for &key in new {
+        if old.contains(&key) || is_modifier(key) {
+            continue;
+        }
+
+        let typed = if shortcut {
+            Typed::Reset
+        } else if key == KeyCode::BSpace {
+            Typed::Backspace
+        } else {
+            Typed::Key { key, shift, alt_gr }
+        };
+
+        // if we fall behind we'll just miss an expansion
+        let _ = TYPED.try_send(typed);
+    }
+}
+
+struct PendingSnippet {
+    trigger: Trigger,
+    next_seq: u8,
+    expansion: heapless::Vec<u8, MAX_EXPANSION>,
+}
+
+struct Snippets {
+    enabled: bool,
+    triggers: SavedTriggers,
+    recent: heapless::Deque<char, { MAX_SNIPPET_TRIGGER + 1 }>,
+    /// Whether the start of the current word has been lost from `recent`,
+    /// either because it was too long or we backspaced into a previous word
+    partial: bool,
+    pending: Option<PendingSnippet>,
+}
+
+fn to_trigger(bytes: &[u8]) -> Option<Trigger> {
+    let s = core::str::from_utf8(bytes).ok()?;
+    Trigger::try_from(s).ok()
+}
+
+impl Snippets {
+    async fn load() -> Self {
+        let enabled = flash::get::<SnippetsEnabled>().await.is_some_and(|e| e.0);
+        let triggers = flash::get::<SavedTriggers>().await.unwrap_or_default();
+
+        crate::log::debug!("Loaded {} snippets, enabled: {}", triggers.0.len(), enabled);
+
+        Self {
+            enabled,
+            triggers,
+            recent: heapless::Deque::new(),
+            partial: false,
+            pending: None,
+        }
+    }
+
+    async fn typed(&mut self, typed: Typed) {
+        if !self.enabled {
+            return;
+        }
+
+        match typed {
+            Typed::Key { key, shift, alt_gr } => {
+                let layout = text::current_layout().await;
+
+                let Some(c) = text::key_to_char(layout, key, shift, alt_gr) else {
+                    self.clear();
+                    return;
+                };
+
+                if TERMINATORS.contains(&c) {
+                    if let Some(trigger) = self.matching_trigger() {
+                        self.expand(trigger, c).await;
+                    }
+
+                    self.clear();
+                    return;
+                }
+
+                if self.recent.is_full() {
+                    self.recent.pop_front();
+                    self.partial = true;
+                }
+                let _ = self.recent.push_back(c);
+            }
+            Typed::Backspace => {
+                if self.recent.pop_back().is_none() {
+                    self.partial = true;
+                }
+            }
+            Typed::Reset => {
+                self.clear();
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.recent.clear();
+        self.partial = false;
+    }
+
+    /// The trigger matching the whole of the word just typed
+    fn matching_trigger(&self) -> Option<Trigger> {
+        if self.partial {
+            return None;
+        }
+
+        self.triggers
+            .0
+            .iter()
+            .find(|t| self.recent.iter().copied().eq(t.chars()))
+            .cloned()
+    }
+
+    async fn expand(&self, trigger: Trigger, terminator: char) {
+        let Some(expansion) = flash::get_keyed::<_, Expansion>(SnippetKey(trigger.clone())).await
+        else {
+            crate::log::warn!("Snippet for {} is missing", trigger.as_str());
+            return;
+        };
+
+        KEYS_HELD.wait_for(|&held| !held).await;
+
+        // anything typed while waiting comes after the terminator, so we'd
+        // remove the wrong characters
+        if !TYPED.is_empty() {
+            crate::log::debug!("Kept typing after {}, not expanding", trigger.as_str());
+            return;
+        }
+
+        // remove the trigger and the terminator we've already typed
+        for _ in 0..trigger.chars().count() + 1 {
+            text::tap(&[KeyCode::BSpace]).await;
+        }
+
+        text::type_text(&expansion).await;
+        text::type_char(text::current_layout().await, terminator).await;
+    }
+
+    async fn save_triggers(&self) {
+        flash::set(&self.triggers).await;
+    }
+
+    async fn command(&mut self, cmd: SnippetCommand) {
+        match cmd {
+            SnippetCommand::Set {
+                trigger,
+                seq,
+                last,
+                chunk,
+            } => {
+                let Some(trigger) = to_trigger(&trigger) else {
+                    crate::log::warn!("Snippet trigger wasn't valid UTF-8");
+                    return;
+                };
+
+                if seq == 0 {
+                    self.pending = Some(PendingSnippet {
+                        trigger: trigger.clone(),
+                        next_seq: 0,
+                        expansion: heapless::Vec::new(),
+                    });
+                }
+
+                let Some(pending) = self
+                    .pending
+                    .as_mut()
+                    .filter(|p| p.trigger == trigger && p.next_seq == seq)
+                else {
+                    crate::log::warn!("Dropping out of order snippet chunk");
+                    self.pending = None;
+                    return;
+                };
+
+                if pending.expansion.extend_from_slice(&chunk).is_err() {
+                    crate::log::warn!("Snippet is longer than {} bytes", MAX_EXPANSION);
+                    self.pending = None;
+                    return;
+                }
+
+                pending.next_seq = pending.next_seq.wrapping_add(1);
+
+                if !last {
+                    return;
+                }
+
+                let Some(pending) = self.pending.take() else {
+                    return;
+                };
+
+                let Ok(expansion) = Expansion::from_utf8(pending.expansion) else {
+                    crate::log::warn!("Snippet wasn't valid UTF-8");
+                    return;
+                };
+
+                if !self.triggers.0.contains(&trigger) {
+                    if self.triggers.0.push(trigger.clone()).is_err() {
+                        crate::log::warn!("Can't store more than {} snippets", MAX_SNIPPETS);
+                        return;
+                    }
+
+                    self.save_triggers().await;
+                }
+
+                flash::set_keyed(SnippetKey(trigger), &expansion).await;
+            }
+            SnippetCommand::Delete(trigger) => {
+                let Some(trigger) = to_trigger(&trigger) else {
+                    return;
+                };
+
+                let Some(idx) = self.triggers.0.iter().position(|t| *t == trigger) else {
+                    return;
+                };
+
+                self.triggers.0.remove(idx);
+                self.save_triggers().await;
+                flash::delete_keyed::<_, Expansion>(SnippetKey(trigger)).await;
+            }
+            SnippetCommand::SetEnabled(enabled) => {
+                self.set_enabled(enabled).await;
+            }
+            SnippetCommand::ToggleEnabled => {
+                self.set_enabled(!self.enabled).await;
+            }
+            SnippetCommand::List => {
+                send_to_host(
+                    DeviceToHostMsg::SnippetsEnabled(self.enabled),
+                    MessageProvenance::Origin,
+                )
+                .await;
+
+                for trigger in &self.triggers.0 {
+                    let trigger = heapless::Vec::from_slice(trigger.as_bytes()).unwrap();
+                    send_to_host(
+                        DeviceToHostMsg::Snippet { trigger },
+                        MessageProvenance::Origin,
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+
+    async fn set_enabled(&mut self, enabled: bool) {
+        crate::log::info!("Snippets enabled: {}", enabled);
+
+        self.enabled = enabled;
+        self.clear();
+        flash::set(&SnippetsEnabled(enabled)).await;
+    }
+}
+
+/// Watches what is typed for snippet triggers and expands them
+#[embassy_executor::task]
+pub async fn snippet_task() {
+    let mut snippets = Snippets::load().await;
+
+    loop {
+        match select(TYPED.receive(), COMMANDS.receive()).await {
+            Either::First(typed) => snippets.typed(typed).await,
+            Either::Second(cmd) => snippets.command(cmd).await,
+        }
+    }
+}
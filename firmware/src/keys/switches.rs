@@ -0,0 +1,79 @@
+//! Where every switch on each side is
+//!
+//! The tables themselves are in [`shared::switches`] so that host tools can
+//! use them too, this adds what only makes sense on the keyboard.
+
+use shared::side::KeyboardSide;
+
+pub use shared::switches::*;
+
+use crate::side::get_side;
+
+/// The switches on this side
+pub fn this_side() -> &'static Switches {
+    if get_side().is_left() {
+        &LEFT
+    } else {
+        &RIGHT
+    }
+}
+
+/// Which side the switch at a keymap (column, row) is on, keys that only
+/// exist in the keymap (chords and such) aren't on either side
+pub fn side_of(logical: (u8, u8)) -> Option<KeyboardSide> {
+    let on = |s: &Switches| s.switches.iter().any(|x| x.logical == logical);
+
+    if on(&LEFT) {
+        Some(KeyboardSide::Left)
+    } else if on(&RIGHT) {
+        Some(KeyboardSide::Right)
+    } else {
+        None
+    }
+}
+
+/// Physical location of the switch at a keymap (column, row), on either side
+pub fn location_of(logical: (u8, u8)) -> Option<(i16, i16)> {
+    LEFT.switches
+        .iter()
+        .chain(RIGHT.switches.iter())
+        .find(|s| s.logical == logical)
+        .map(|s| s.location)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Finger {
+    Pinky,
+    Ring,
+    Middle,
+    Index,
+    Thumb,
+}
+
+/// Which finger is expected to press the switch at a keymap (column, row),
+/// assuming the usual one finger per column with the pinkies covering the two
+/// outer columns and the index fingers the two inner ones
+pub fn finger(logical: (u8, u8)) -> Option<(KeyboardSide, Finger)> {
+    let side = side_of(logical)?;
+    let (col, row) = logical;
+
+    // the thumb clusters are patched in below the main keys
+    if row >= 6 {
+        return Some((side, Finger::Thumb));
+    }
+
+    let from_edge = if side.is_left() {
+        col as usize
+    } else {
+        LAYOUT_COLS - 1 - col as usize
+    };
+
+    let finger = match from_edge {
+        0 | 1 => Finger::Pinky,
+        2 => Finger::Ring,
+        3 => Finger::Middle,
+        _ => Finger::Index,
+    };
+
+    Some((side, finger))
+}
@@ -0,0 +1,322 @@
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use keyberon::key_code::KeyCode;
+
+use crate::{
+    state::{current_host, HostId},
+    sync::{mutex, Mutex},
+};
+
+use super::{press_synthetic_keycodes, unicode};
+
+/// The keyboard layout the host has configured, we need to know this to pick
+/// the right keys to type a character
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub enum HostLayout {
+    Us,
+    Uk,
+    De,
+    Fr,
+    Dvorak,
+}
+
+impl HostLayout {
+    const ALL: [HostLayout; 5] = [
+        HostLayout::Us,
+        HostLayout::Uk,
+        HostLayout::De,
+        HostLayout::Fr,
+        HostLayout::Dvorak,
+    ];
+
+    fn table(self) -> &'static [LayoutKey] {
+        match self {
+            HostLayout::Us => US,
+            HostLayout::Uk => UK,
+            HostLayout::De => DE,
+            HostLayout::Fr => FR,
+            HostLayout::Dvorak => DVORAK,
+        }
+    }
+}
+
+/// What a key types on the host with no modifiers, shift and AltGr held
+struct LayoutKey {
+    key: KeyCode,
+    base: char,
+    shift: char,
+    alt_gr: char,
+}
+
+const NONE: char = '\0';
+
+const fn k(key: KeyCode, base: char, shift: char) -> LayoutKey {
+    LayoutKey {
+        key,
+        base,
+        shift,
+        alt_gr: NONE,
+    }
+}
+
+const fn ka(key: KeyCode, base: char, shift: char, alt_gr: char) -> LayoutKey {
+    LayoutKey {
+        key,
+        base,
+        shift,
+        alt_gr,
+    }
+}
+
+// Each table lists the keys that don't type their US letter or digit legend,
+// anything not listed falls back to the US letter and digit positions. Dead
+// keys are left out, characters that need them go through unicode input.
+
+#[rustfmt::skip]
+const US: &[LayoutKey] = &[
+    k(KeyCode::Kb1, '1', '!'), k(KeyCode::Kb2, '2', '@'), k(KeyCode::Kb3, '3', '#'),
+    k(KeyCode::Kb4, '4', '$'), k(KeyCode::Kb5, '5', '%'), k(KeyCode::Kb6, '6', '^'),
+    k(KeyCode::Kb7, '7', '&'), k(KeyCode::Kb8, '8', '*'), k(KeyCode::Kb9, '9', '('),
+    k(KeyCode::Kb0, '0', ')'),
+    k(KeyCode::Minus, '-', '_'), k(KeyCode::Equal, '=', '+'),
+    k(KeyCode::LBracket, '[', '{'), k(KeyCode::RBracket, ']', '}'), k(KeyCode::Bslash, '\\', '|'),
+    k(KeyCode::SColon, ';', ':'), k(KeyCode::Quote, '\'', '"'), k(KeyCode::Grave, '`', '~'),
+    k(KeyCode::Comma, ',', '<'), k(KeyCode::Dot, '.', '>'), k(KeyCode::Slash, '/', '?'),
+];
+
+#[rustfmt::skip]
+const UK: &[LayoutKey] = &[
+    k(KeyCode::Kb1, '1', '!'), k(KeyCode::Kb2, '2', '"'), k(KeyCode::Kb3, '3', '£'),
+    ka(KeyCode::Kb4, '4', '$', '€'), k(KeyCode::Kb5, '5', '%'), k(KeyCode::Kb6, '6', '^'),
+    k(KeyCode::Kb7, '7', '&'), k(KeyCode::Kb8, '8', '*'), k(KeyCode::Kb9, '9', '('),
+    k(KeyCode::Kb0, '0', ')'),
+    k(KeyCode::Minus, '-', '_'), k(KeyCode::Equal, '=', '+'),
+    k(KeyCode::LBracket, '[', '{'), k(KeyCode::RBracket, ']', '}'),
+    k(KeyCode::NonUsHash, '#', '~'), k(KeyCode::NonUsBslash, '\\', '|'),
+    k(KeyCode::SColon, ';', ':'), k(KeyCode::Quote, '\'', '@'), k(KeyCode::Grave, '`', '¬'),
+    k(KeyCode::Comma, ',', '<'), k(KeyCode::Dot, '.', '>'), k(KeyCode::Slash, '/', '?'),
+];
+
+#[rustfmt::skip]
+const DE: &[LayoutKey] = &[
+    k(KeyCode::Y, 'z', 'Z'), k(KeyCode::Z, 'y', 'Y'),
+    ka(KeyCode::Q, 'q', 'Q', '@'), ka(KeyCode::E, 'e', 'E', '€'), ka(KeyCode::M, 'm', 'M', 'µ'),
+    k(KeyCode::Kb1, '1', '!'), ka(KeyCode::Kb2, '2', '"', '²'), ka(KeyCode::Kb3, '3', '§', '³'),
+    k(KeyCode::Kb4, '4', '$'), k(KeyCode::Kb5, '5', '%'), k(KeyCode::Kb6, '6', '&'),
+    ka(KeyCode::Kb7, '7', '/', '{'), ka(KeyCode::Kb8, '8', '(', '['), ka(KeyCode::Kb9, '9', ')', ']'),
+    ka(KeyCode::Kb0, '0', '=', '}'),
+    ka(KeyCode::Minus, 'ß', '?', '\\'),
+    k(KeyCode::LBracket, 'ü', 'Ü'), ka(KeyCode::RBracket, '+', '*', '~'), k(KeyCode::NonUsHash, '#', '\''),
+    k(KeyCode::SColon, 'ö', 'Ö'), k(KeyCode::Quote, 'ä', 'Ä'), k(KeyCode::Grave, NONE, '°'),
+    ka(KeyCode::NonUsBslash, '<', '>', '|'),
+    k(KeyCode::Comma, ',', ';'), k(KeyCode::Dot, '.', ':'), k(KeyCode::Slash, '-', '_'),
+];
+
+#[rustfmt::skip]
+const FR: &[LayoutKey] = &[
+    k(KeyCode::Q, 'a', 'A'), k(KeyCode::A, 'q', 'Q'), k(KeyCode::W, 'z', 'Z'), k(KeyCode::Z, 'w', 'W'),
+    k(KeyCode::SColon, 'm', 'M'), ka(KeyCode::E, 'e', 'E', '€'),
+    k(KeyCode::Kb1, '&', '1'), k(KeyCode::Kb2, 'é', '2'), ka(KeyCode::Kb3, '"', '3', '#'),
+    ka(KeyCode::Kb4, '\'', '4', '{'), ka(KeyCode::Kb5, '(', '5', '['), ka(KeyCode::Kb6, '-', '6', '|'),
+    k(KeyCode::Kb7, 'è', '7'), ka(KeyCode::Kb8, '_', '8', '\\'), ka(KeyCode::Kb9, 'ç', '9', '^'),
+    ka(KeyCode::Kb0, 'à', '0', '@'),
+    ka(KeyCode::Minus, ')', '°', ']'), ka(KeyCode::Equal, '=', '+', '}'),
+    ka(KeyCode::RBracket, '$', '£', '¤'), k(KeyCode::Quote, 'ù', '%'), k(KeyCode::NonUsHash, '*', 'µ'),
+    k(KeyCode::NonUsBslash, '<', '>'), k(KeyCode::Grave, '²', NONE),
+    k(KeyCode::M, ',', '?'), k(KeyCode::Comma, ';', '.'), k(KeyCode::Dot, ':', '/'),
+    k(KeyCode::Slash, '!', '§'),
+];
+
+#[rustfmt::skip]
+const DVORAK: &[LayoutKey] = &[
+    k(KeyCode::Kb1, '1', '!'), k(KeyCode::Kb2, '2', '@'), k(KeyCode::Kb3, '3', '#'),
+    k(KeyCode::Kb4, '4', '$'), k(KeyCode::Kb5, '5', '%'), k(KeyCode::Kb6, '6', '^'),
+    k(KeyCode::Kb7, '7', '&'), k(KeyCode::Kb8, '8', '*'), k(KeyCode::Kb9, '9', '('),
+    k(KeyCode::Kb0, '0', ')'),
+    k(KeyCode::Minus, '[', '{'), k(KeyCode::Equal, ']', '}'), k(KeyCode::Grave, '`', '~'),
+    k(KeyCode::Q, '\'', '"'), k(KeyCode::W, ',', '<'), k(KeyCode::E, '.', '>'),
+    k(KeyCode::R, 'p', 'P'), k(KeyCode::T, 'y', 'Y'), k(KeyCode::Y, 'f', 'F'),
+    k(KeyCode::U, 'g', 'G'), k(KeyCode::I, 'c', 'C'), k(KeyCode::O, 'r', 'R'),
+    k(KeyCode::P, 'l', 'L'), k(KeyCode::LBracket, '/', '?'), k(KeyCode::RBracket, '=', '+'),
+    k(KeyCode::Bslash, '\\', '|'),
+    k(KeyCode::A, 'a', 'A'), k(KeyCode::S, 'o', 'O'), k(KeyCode::D, 'e', 'E'),
+    k(KeyCode::F, 'u', 'U'), k(KeyCode::G, 'i', 'I'), k(KeyCode::H, 'd', 'D'),
+    k(KeyCode::J, 'h', 'H'), k(KeyCode::K, 't', 'T'), k(KeyCode::L, 'n', 'N'),
+    k(KeyCode::SColon, 's', 'S'), k(KeyCode::Quote, '-', '_'),
+    k(KeyCode::Z, ';', ':'), k(KeyCode::X, 'q', 'Q'), k(KeyCode::C, 'j', 'J'),
+    k(KeyCode::V, 'k', 'K'), k(KeyCode::B, 'x', 'X'), k(KeyCode::N, 'b', 'B'),
+    k(KeyCode::M, 'm', 'M'), k(KeyCode::Comma, 'w', 'W'), k(KeyCode::Dot, 'v', 'V'),
+    k(KeyCode::Slash, 'z', 'Z'),
+];
+
+/// Find the keys to press together to type `c` on a host using `layout`
+pub fn char_to_keys(layout: HostLayout, c: char) -> Option<heapless::Vec<KeyCode, 3>> {
+    use KeyCode::*;
+
+    const LETTERS: [KeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [KeyCode; 10] = [Kb0, Kb1, Kb2, Kb3, Kb4, Kb5, Kb6, Kb7, Kb8, Kb9];
+
+    let mut out = heapless::Vec::new();
+
+    if c == NONE {
+        return None;
+    }
+
+    let (key, shift, alt_gr) = if let Some(lk) = layout.table().iter().find(|lk| lk.base == c) {
+        (lk.key, false, false)
+    } else if let Some(lk) = layout.table().iter().find(|lk| lk.shift == c) {
+        (lk.key, true, false)
+    } else if let Some(lk) = layout.table().iter().find(|lk| lk.alt_gr == c) {
+        (lk.key, false, true)
+    } else {
+        match c {
+            'a'..='z' => (LETTERS[c as usize - 'a' as usize], false, false),
+            'A'..='Z' => (LETTERS[c as usize - 'A' as usize], true, false),
+            '0'..='9' => (DIGITS[c as usize - '0' as usize], false, false),
+            ' ' => (Space, false, false),
+            '\n' => (Enter, false, false),
+            '\t' => (Tab, false, false),
+            _ => return None,
+        }
+    };
+
+    if shift {
+        let _ = out.push(LShift);
+    }
+    if alt_gr {
+        let _ = out.push(RAlt);
+    }
+    let _ = out.push(key);
+
+    Some(out)
+}
+
+/// Find the character the host types when `key` is pressed, the reverse of
+/// [`char_to_keys`]
+pub fn key_to_char(layout: HostLayout, key: KeyCode, shift: bool, alt_gr: bool) -> Option<char> {
+    use KeyCode::*;
+
+    let c = if let Some(lk) = layout.table().iter().find(|lk| lk.key == key) {
+        match (shift, alt_gr) {
+            (false, false) => lk.base,
+            (true, false) => lk.shift,
+            (false, true) => lk.alt_gr,
+            (true, true) => NONE,
+        }
+    } else if alt_gr {
+        NONE
+    } else {
+        match key {
+            _ if (A as u8..=Z as u8).contains(&(key as u8)) => {
+                let c = (b'a' + (key as u8 - A as u8)) as char;
+                if shift {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                }
+            }
+            _ if (Kb1 as u8..=Kb9 as u8).contains(&(key as u8)) && !shift => {
+                (b'1' + (key as u8 - Kb1 as u8)) as char
+            }
+            Kb0 if !shift => '0',
+            Space => ' ',
+            Enter => '\n',
+            Tab => '\t',
+            _ => NONE,
+        }
+    };
+
+    (c != NONE).then_some(c)
+}
+
+/// The user's choice of layout for a host, stored in flash
+#[derive(serde::Serialize)]
+struct LayoutSetting(HostId);
+
+static LAYOUT_CACHE: Mutex<Option<(HostId, HostLayout)>> = mutex(None);
+
+/// The layout of the host key presses are currently going to, US unless
+/// the user has picked another
+pub async fn current_layout() -> HostLayout {
+    let host = current_host();
+    let mut cache = LAYOUT_CACHE.lock().await;
+
+    if let Some((h, layout)) = *cache {
+        if h == host {
+            return layout;
+        }
+    }
+
+    let layout = crate::flash::get_keyed(LayoutSetting(host))
+        .await
+        .unwrap_or(HostLayout::Us);
+    *cache = Some((host, layout));
+
+    layout
+}
+
+async fn cycle_layout() {
+    let host = current_host();
+    let current = current_layout().await;
+
+    let idx = HostLayout::ALL
+        .iter()
+        .position(|&l| l == current)
+        .unwrap_or(0);
+    let next = HostLayout::ALL[(idx + 1) % HostLayout::ALL.len()];
+
+    crate::log::info!("Host layout for {} set to {}", host, next);
+    crate::flash::set_keyed(LayoutSetting(host), &next).await;
+    *LAYOUT_CACHE.lock().await = Some((host, next));
+}
+
+pub async fn tap(keys: &[KeyCode]) {
+    press_synthetic_keycodes(keys.iter().copied()).await;
+    press_synthetic_keycodes([]).await;
+}
+
+/// Type out `s` using the host's keyboard layout, characters the layout can't
+/// type are sent with unicode input instead
+pub async fn type_text(s: &str) {
+    let layout = current_layout().await;
+
+    for c in s.chars() {
+        type_char(layout, c).await;
+    }
+}
+
+pub async fn type_char(layout: HostLayout, c: char) {
+    if let Some(keys) = char_to_keys(layout, c) {
+        tap(&keys).await;
+    } else {
+        let mut buf = [0u8; 4];
+        unicode::type_unicode(c.encode_utf8(&mut buf)).await;
+    }
+}
+
+enum TextCommand {
+    Type(&'static str),
+    CycleLayout,
+}
+
+static TEXT_COMMANDS: Channel<ThreadModeRawMutex, TextCommand, 4> = Channel::new();
+
+pub async fn send_text(s: &'static str) {
+    TEXT_COMMANDS.send(TextCommand::Type(s)).await;
+}
+
+pub async fn cycle_host_layout() {
+    TEXT_COMMANDS.send(TextCommand::CycleLayout).await;
+}
+
+#[embassy_executor::task]
+pub async fn text_task() {
+    loop {
+        match TEXT_COMMANDS.receive().await {
+            TextCommand::Type(s) => type_text(s).await,
+            TextCommand::CycleLayout => cycle_layout().await,
+        }
+    }
+}
@@ -1,43 +1,110 @@
 use embassy_os_guess::OS;
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
-use usbd_human_interface_device::{device::keyboard::NKROBootKeyboardReport, page::Keyboard};
+use usbd_human_interface_device::page::Keyboard;
 
-use crate::ble::hid::publish_keyboard_report;
+use crate::state::{current_host, HostId};
 use crate::usb::guessed_host_os;
 
-use super::UnicodeMode;
+use super::{press_synthetic, UnicodeMode};
 
-static UNICODE_MESSAGES: Channel<ThreadModeRawMutex, &'static str, 4> = Channel::new();
+enum UnicodeCommand {
+    Type(&'static str),
+    CycleMode,
+}
+
+static UNICODE_COMMANDS: Channel<ThreadModeRawMutex, UnicodeCommand, 4> = Channel::new();
 
 pub async fn send_unicode(msg: &'static str) {
-    UNICODE_MESSAGES.send(msg).await;
+    UNICODE_COMMANDS.send(UnicodeCommand::Type(msg)).await;
+}
+
+pub async fn cycle_unicode_mode() {
+    UNICODE_COMMANDS.send(UnicodeCommand::CycleMode).await;
 }
 
 #[embassy_executor::task]
 pub async fn unicode_task() {
     loop {
-        let msg = UNICODE_MESSAGES.receive().await;
+        match UNICODE_COMMANDS.receive().await {
+            UnicodeCommand::Type(msg) => type_unicode(msg).await,
+            UnicodeCommand::CycleMode => cycle_mode().await,
+        }
+    }
+}
 
-        let mode = match guessed_host_os() {
+/// The user's choice of unicode mode for a host, stored in flash
+#[derive(serde::Serialize)]
+struct ModeOverride(HostId);
+
+impl UnicodeMode {
+    const ALL: [UnicodeMode; 5] = [
+        UnicodeMode::Linux,
+        UnicodeMode::Mac,
+        UnicodeMode::WinCompose,
+        UnicodeMode::WinHexNumpad,
+        UnicodeMode::Emacs,
+    ];
+
+    fn guessed() -> Self {
+        match guessed_host_os() {
             Some(OS::Linux) => UnicodeMode::Linux,
+            Some(OS::Windows) => UnicodeMode::WinCompose,
             _ => UnicodeMode::Mac,
-        };
-
-        match mode {
-            UnicodeMode::Linux => emit_linux(msg).await,
-            UnicodeMode::Mac => emit_mac(msg).await,
         }
     }
 }
 
+async fn mode_override(host: HostId) -> Option<UnicodeMode> {
+    crate::flash::get_keyed(ModeOverride(host)).await
+}
+
+/// Step the current host's override through each mode, then back to using
+/// the OS guess
+async fn cycle_mode() {
+    let host = current_host();
+
+    let next = match mode_override(host).await {
+        None => UnicodeMode::ALL.first(),
+        Some(m) => UnicodeMode::ALL.iter().skip_while(|&&x| x != m).nth(1),
+    };
+
+    if let Some(mode) = next {
+        crate::log::info!("Unicode mode for {} set to {}", host, mode);
+        crate::flash::set_keyed(ModeOverride(host), mode).await;
+    } else {
+        crate::log::info!("Unicode mode for {} set to automatic", host);
+        crate::flash::delete_keyed::<_, UnicodeMode>(ModeOverride(host)).await;
+    }
+}
+
+/// The mode to use for the current host, the user's override if they've
+/// picked one, otherwise from the guessed OS
+async fn current_mode() -> UnicodeMode {
+    match mode_override(current_host()).await {
+        Some(m) => m,
+        None => UnicodeMode::guessed(),
+    }
+}
+
+/// Type out `msg` using the host's unicode input method, waiting until it has
+/// been sent
+pub async fn type_unicode(msg: &str) {
+    match current_mode().await {
+        UnicodeMode::Linux => emit_linux(msg).await,
+        UnicodeMode::Mac => emit_mac(msg).await,
+        UnicodeMode::WinCompose => emit_wincompose(msg).await,
+        UnicodeMode::WinHexNumpad => emit_win_hex_numpad(msg).await,
+        UnicodeMode::Emacs => emit_emacs(msg).await,
+    }
+}
+
 async fn press_keys(keys: &[Keyboard]) {
-    publish_keyboard_report(NKROBootKeyboardReport::new(keys.iter().copied())).await;
+    press_synthetic(keys.iter().copied()).await;
 }
 
-#[allow(unused)]
 async fn tap_keys(keys: &[Keyboard]) {
     press_keys(keys).await;
-    publish_keyboard_report(NKROBootKeyboardReport::new([])).await;
+    press_synthetic([]).await;
 }
 
 const HEX_KEYS: [Keyboard; 16] = [
@@ -126,3 +193,61 @@ async fn emit_mac(msg: &str) {
     }
     press_keys(&[]).await;
 }
+
+async fn emit_wincompose(msg: &str) {
+    for c in msg.chars() {
+        tap_keys(&[Keyboard::RightAlt]).await;
+        tap_keys(&[Keyboard::U]).await;
+
+        for k in to_escape(c) {
+            tap_keys(&[k]).await;
+        }
+
+        tap_keys(&[Keyboard::ReturnEnter]).await;
+    }
+}
+
+fn to_numpad(k: Keyboard) -> Keyboard {
+    match k {
+        Keyboard::Keyboard0 => Keyboard::Keypad0,
+        Keyboard::Keyboard1 => Keyboard::Keypad1,
+        Keyboard::Keyboard2 => Keyboard::Keypad2,
+        Keyboard::Keyboard3 => Keyboard::Keypad3,
+        Keyboard::Keyboard4 => Keyboard::Keypad4,
+        Keyboard::Keyboard5 => Keyboard::Keypad5,
+        Keyboard::Keyboard6 => Keyboard::Keypad6,
+        Keyboard::Keyboard7 => Keyboard::Keypad7,
+        Keyboard::Keyboard8 => Keyboard::Keypad8,
+        Keyboard::Keyboard9 => Keyboard::Keypad9,
+        k => k,
+    }
+}
+
+async fn emit_win_hex_numpad(msg: &str) {
+    for c in msg.chars() {
+        press_keys(&[Keyboard::LeftAlt]).await;
+        press_keys(&[Keyboard::LeftAlt, Keyboard::KeypadAdd]).await;
+        press_keys(&[Keyboard::LeftAlt]).await;
+
+        for k in to_escape(c) {
+            press_keys(&[Keyboard::LeftAlt, to_numpad(k)]).await;
+            press_keys(&[Keyboard::LeftAlt]).await;
+        }
+
+        press_keys(&[]).await;
+    }
+}
+
+async fn emit_emacs(msg: &str) {
+    for c in msg.chars() {
+        tap_keys(&[Keyboard::LeftControl, Keyboard::X]).await;
+        tap_keys(&[Keyboard::Keyboard8]).await;
+        tap_keys(&[Keyboard::ReturnEnter]).await;
+
+        for k in to_escape(c) {
+            tap_keys(&[k]).await;
+        }
+
+        tap_keys(&[Keyboard::ReturnEnter]).await;
+    }
+}
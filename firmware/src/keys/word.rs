@@ -0,0 +1,214 @@
+use embassy_time::{Duration, Instant};
+use keyberon::{action::Action, key_code::KeyCode, layout::Event};
+
+use super::layout::LAYERS;
+
+/// Which keys keep a word mode going and which end it
+///
+/// Modifiers are passed through without affecting the mode. Keys in neither
+/// set are too, unless `break_on_others` is set, but only continue keys reset
+/// the idle timeout
+pub struct WordConfig {
+    pub continue_keys: &'static [KeyCode],
+    pub break_keys: &'static [KeyCode],
+    pub break_on_others: bool,
+}
+
+const PUNCTUATION: &[KeyCode] = &[
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Escape,
+    KeyCode::Comma,
+    KeyCode::Dot,
+    KeyCode::Slash,
+    KeyCode::SColon,
+    KeyCode::Quote,
+    KeyCode::Grave,
+    KeyCode::LBracket,
+    KeyCode::RBracket,
+    KeyCode::Bslash,
+    KeyCode::Equal,
+];
+
+#[rustfmt::skip]
+pub const CAPS_WORD: WordConfig = WordConfig {
+    continue_keys: &[
+        KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+        KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+        KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+        KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+        KeyCode::Kb1, KeyCode::Kb2, KeyCode::Kb3, KeyCode::Kb4, KeyCode::Kb5,
+        KeyCode::Kb6, KeyCode::Kb7, KeyCode::Kb8, KeyCode::Kb9, KeyCode::Kb0,
+        KeyCode::Minus, KeyCode::BSpace, KeyCode::Delete,
+    ],
+    break_keys: PUNCTUATION,
+    break_on_others: false,
+};
+
+#[rustfmt::skip]
+pub const NUM_WORD: WordConfig = WordConfig {
+    continue_keys: &[
+        KeyCode::Kb1, KeyCode::Kb2, KeyCode::Kb3, KeyCode::Kb4, KeyCode::Kb5,
+        KeyCode::Kb6, KeyCode::Kb7, KeyCode::Kb8, KeyCode::Kb9, KeyCode::Kb0,
+        KeyCode::BSpace, KeyCode::Delete,
+    ],
+    break_keys: PUNCTUATION,
+    break_on_others: true,
+};
+
+/// The layer held by num word
+pub const NUM_LAYER: usize = 2;
+
+/// Word modes turn themselves off if no continue key is pressed for this long
+pub const WORD_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SHIFTED_BY_CAPS_WORD: &[KeyCode] = &[KeyCode::Minus];
+
+fn is_letter(k: KeyCode) -> bool {
+    (KeyCode::A as u8..=KeyCode::Z as u8).contains(&(k as u8))
+}
+
+fn is_modifier(k: KeyCode) -> bool {
+    (KeyCode::LCtrl as u8..=KeyCode::RGui as u8).contains(&(k as u8))
+}
+
+fn is_shift(k: KeyCode) -> bool {
+    matches!(k, KeyCode::LShift | KeyCode::RShift)
+}
+
+enum Step {
+    Continue,
+    Break,
+    Ignore,
+}
+
+impl WordConfig {
+    fn step(&self, k: KeyCode) -> Step {
+        if is_modifier(k) {
+            Step::Ignore
+        } else if self.break_keys.contains(&k) {
+            Step::Break
+        } else if self.continue_keys.contains(&k) {
+            Step::Continue
+        } else if self.break_on_others {
+            Step::Break
+        } else {
+            Step::Ignore
+        }
+    }
+}
+
+/// Caps word and num word, these sit between the keycodes produced by the
+/// layout and the report sent to the host
+pub struct WordModes {
+    caps_word: bool,
+    num_word: bool,
+    last_activity: Instant,
+    last_keycodes: heapless::Vec<KeyCode, 24>,
+}
+
+impl Default for WordModes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WordModes {
+    pub fn new() -> Self {
+        Self {
+            caps_word: false,
+            num_word: false,
+            last_activity: Instant::now(),
+            last_keycodes: heapless::Vec::new(),
+        }
+    }
+
+    pub fn toggle_caps_word(&mut self) {
+        self.caps_word = !self.caps_word;
+        self.last_activity = Instant::now();
+    }
+
+    pub fn toggle_num_word(&mut self) {
+        self.num_word = !self.num_word;
+        self.last_activity = Instant::now();
+    }
+
+    pub fn caps_word(&self) -> bool {
+        self.caps_word
+    }
+
+    /// The layer that should be the default layer, if any
+    pub fn layer(&self) -> Option<usize> {
+        self.num_word.then_some(NUM_LAYER)
+    }
+
+    /// Called before an event is passed to the layout
+    ///
+    /// A key that does nothing on the num layer ends num word so that it
+    /// resolves on the base layer instead
+    pub fn event(&mut self, evt: Event) {
+        let Event::Press(row, col) = evt else {
+            return;
+        };
+
+        if !self.num_word {
+            return;
+        }
+
+        let action = &LAYERS[NUM_LAYER][row as usize][col as usize];
+
+        if matches!(action, Action::NoOp | Action::Trans) {
+            self.num_word = false;
+        }
+    }
+
+    /// Update the word modes from newly pressed keys and apply caps word to
+    /// the keycodes
+    pub fn process(&mut self, keycodes: &mut heapless::Vec<KeyCode, 24>) {
+        if self.last_activity.elapsed() > WORD_TIMEOUT {
+            self.caps_word = false;
+            self.num_word = false;
+        }
+
+        for &k in keycodes.iter() {
+            if self.last_keycodes.contains(&k) {
+                continue;
+            }
+
+            if self.caps_word {
+                match CAPS_WORD.step(k) {
+                    Step::Continue => self.last_activity = Instant::now(),
+                    Step::Break => self.caps_word = false,
+                    Step::Ignore => {}
+                }
+            }
+
+            if self.num_word {
+                match NUM_WORD.step(k) {
+                    Step::Continue => self.last_activity = Instant::now(),
+                    Step::Break => self.num_word = false,
+                    Step::Ignore => {}
+                }
+            }
+        }
+
+        self.last_keycodes = keycodes.clone();
+
+        if !self.caps_word {
+            return;
+        }
+
+        // shortcuts like ctrl+c are left alone, adding shift would make them a
+        // different shortcut. other keys still held from rolling over don't
+        // stop the next letter being shifted
+        let shortcut = keycodes.iter().any(|&k| is_modifier(k) && !is_shift(k));
+        let any_shifted = keycodes
+            .iter()
+            .any(|&k| is_letter(k) || SHIFTED_BY_CAPS_WORD.contains(&k));
+
+        if any_shifted && !shortcut && !keycodes.contains(&KeyCode::LShift) {
+            let _ = keycodes.push(KeyCode::LShift);
+        }
+    }
+}
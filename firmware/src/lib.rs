@@ -35,7 +35,10 @@ use panic_reset as _;
 use usb::VBUS_DETECT;
 use utils::log;
 
-use crate::keys::ScannerInstance;
+use crate::keys::{
+    scan::{ArrayMatrix, DiodeDirection},
+    ScannerInstance, MATRIX_SETTLE_NS,
+};
 
 pub mod ble;
 mod flash;
@@ -254,16 +257,19 @@ pub async fn main(spawner: Spawner) {
 
     flash::init(flash_mutex).await;
 
-    rgb::init(&spawner, p.PWM0, pins::take_leds!(p).degrade());
+    rgb::init(
+        &spawner,
+        p.PWM0,
+        pins::take_leds!(p).degrade(),
+        pins::take_leds_pwr!(p).degrade(),
+    );
     // rgb::init(&spawner, p.I2S, pins::take_leds!(p).degrade(), I2SIrqs,
     //           p.P0_20.degrade(), p.P0_21.degrade(),
     //           p.P0_22.degrade()
     // );
-    let mut leds_on = Output::new(pins::take_leds_pwr!(p), Level::High, OutputDrive::HighDrive);
-    leds_on.set_high();
 
-    let scanner = ScannerInstance::new(
-        (
+    let scanner = ScannerInstance::new(ArrayMatrix::new(
+        [
             Output::new(pins::take_col_0!(p), Level::Low, OutputDrive::Standard),
             Output::new(pins::take_col_1!(p), Level::Low, OutputDrive::Standard),
             Output::new(pins::take_col_2!(p), Level::Low, OutputDrive::Standard),
@@ -271,16 +277,18 @@ pub async fn main(spawner: Spawner) {
             Output::new(pins::take_col_4!(p), Level::Low, OutputDrive::Standard),
             Output::new(pins::take_col_5!(p), Level::Low, OutputDrive::Standard),
             Output::new(pins::take_col_6!(p), Level::Low, OutputDrive::Standard),
-        ),
-        (
+        ],
+        [
             Input::new(pins::take_row_0!(p), Pull::Down),
             Input::new(pins::take_row_1!(p), Pull::Down),
             Input::new(pins::take_row_2!(p), Pull::Down),
             Input::new(pins::take_row_3!(p), Pull::Down),
             Input::new(pins::take_row_4!(p), Pull::Down),
             Input::new(pins::take_row_5!(p), Pull::Down),
-        ),
-    );
+        ],
+        DiodeDirection::Col2Row,
+        MATRIX_SETTLE_NS,
+    ));
 
     keys::init(&spawner, scanner);
 
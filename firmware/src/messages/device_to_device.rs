@@ -1,8 +1,15 @@
 use core::hash::Hash;
 use serde::{Deserialize, Serialize};
-use shared::{device_to_host::DeviceToHost, host_to_device::HostToDeviceMsg};
+use shared::{
+    device_to_host::DeviceToHost,
+    host_to_device::{HostToDeviceMsg, MAX_DIRECT_LEDS_CHUNK},
+    rgb::{Notification, Rgb},
+};
 
-use crate::rgb::animations::AnimationSync;
+use crate::{
+    keys::{indicators::Indicators, oneshot::OneShotState},
+    rgb::animations::AnimationSync,
+};
 
 #[derive(
     Serialize,
@@ -24,4 +31,15 @@ pub enum DeviceToDevice {
     KeyRelease(u8, u8),
     SetAnimation(AnimationSync),
     SyncAnimation(AnimationSync),
+    OneShotState(OneShotState),
+    SetBrightness(u8),
+    CurrentLayer(u8),
+    Indicators(Indicators),
+    /// The other side's part of a direct LED frame from the host, see
+    /// [`HostToDeviceMsg::DirectLeds`]
+    DirectLeds {
+        offset: u8,
+        colours: heapless::Vec<Rgb, MAX_DIRECT_LEDS_CHUNK>,
+    },
+    Notify(Notification),
 }
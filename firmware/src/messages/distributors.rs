@@ -1,8 +1,9 @@
 use shared::device_to_host::{DeviceToHost, DeviceToHostMsg};
 use shared::host_to_device::HostToDeviceMsg;
 
+use crate::keys::snippets::SnippetCommand;
 use crate::side;
-use crate::{interboard, usb};
+use crate::{interboard, keys, metrics, rgb, usb};
 
 use super::device_to_device::DeviceToDevice;
 
@@ -13,18 +14,116 @@ pub async fn from_usb_distributor() {
     loop {
         let msg = sub.next_message_pure().await;
 
-        if msg.targets_side(side::get_side()) {
-            #[allow(unreachable_code)]
+        // direct LED frames cover both sides whichever side they target, we
+        // keep our own LEDs and pass the rest on rather than forwarding them
+        let direct = matches!(msg.msg, HostToDeviceMsg::DirectLeds { .. });
+
+        if direct || msg.targets_side(side::get_side()) {
             handle_from_host(msg.msg.clone()).await;
         }
-        if msg.targets_side(side::get_other_side()) {
+
+        if !direct && msg.targets_side(side::get_other_side()) {
             interboard::send_msg(DeviceToDevice::ForwardedFromHost(msg.msg), 2).await;
         }
     }
 }
 
 async fn handle_from_host(msg: HostToDeviceMsg) {
-    match msg {}
+    match msg {
+        HostToDeviceMsg::TypeText {
+            id,
+            seq,
+            last,
+            chunk,
+        } => {
+            if side::is_master() {
+                keys::host_text::receive_chunk(id, seq, last, &chunk);
+            }
+        }
+        HostToDeviceMsg::CancelTyping => {
+            if side::is_master() {
+                keys::host_text::cancel();
+            }
+        }
+        HostToDeviceMsg::SetSnippet {
+            trigger,
+            seq,
+            last,
+            chunk,
+        } => {
+            if side::is_master() {
+                keys::snippets::send_command(SnippetCommand::Set {
+                    trigger,
+                    seq,
+                    last,
+                    chunk,
+                })
+                .await;
+            }
+        }
+        HostToDeviceMsg::DeleteSnippet { trigger } => {
+            if side::is_master() {
+                keys::snippets::send_command(SnippetCommand::Delete(trigger)).await;
+            }
+        }
+        HostToDeviceMsg::SetSnippetsEnabled(enabled) => {
+            if side::is_master() {
+                keys::snippets::send_command(SnippetCommand::SetEnabled(enabled)).await;
+            }
+        }
+        HostToDeviceMsg::ListSnippets => {
+            if side::is_master() {
+                keys::snippets::send_command(SnippetCommand::List).await;
+            }
+        }
+        HostToDeviceMsg::SetDebounce(config) => {
+            keys::scan::set_debounce(config).await;
+        }
+        HostToDeviceMsg::GetKeyCounts => {
+            if side::is_master() {
+                metrics::send_key_counts().await;
+            }
+        }
+        HostToDeviceMsg::SetAnimation(choice) => {
+            if side::is_master() {
+                rgb::control_animation(rgb::AnimationControl::Set(choice)).await;
+            }
+        }
+        HostToDeviceMsg::SetRandomizerPolicy(policy) => {
+            if side::is_master() {
+                rgb::control_animation(rgb::AnimationControl::SetPolicy(policy)).await;
+            }
+        }
+        HostToDeviceMsg::SetBrightness(level) => {
+            if side::is_master() {
+                rgb::brightness::set(level).await;
+            }
+        }
+        HostToDeviceMsg::SetPowerBudget(budget) => {
+            rgb::power::set_budget(budget).await;
+        }
+        HostToDeviceMsg::SetLedIdleTimeout(timeout) => {
+            rgb::idle::set_timeout(timeout).await;
+        }
+        HostToDeviceMsg::SetKeyColours {
+            layer,
+            row,
+            colours,
+        } => {
+            rgb::key_colours::set_row(layer, row, colours).await;
+        }
+        HostToDeviceMsg::ResetKeyColours => {
+            rgb::key_colours::reset().await;
+        }
+        HostToDeviceMsg::DirectLeds { offset, colours } => {
+            rgb::direct::receive_from_host(offset, &colours).await;
+        }
+        HostToDeviceMsg::Notify(notification) => {
+            if side::is_master() {
+                rgb::notification::show(notification).await;
+            }
+        }
+    }
 }
 
 #[embassy_executor::task]
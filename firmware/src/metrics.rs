@@ -1,29 +1,299 @@
 use core::num::Wrapping;
 
 use embassy_executor::Spawner;
-use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, pubsub::PubSubChannel};
-use embassy_time::Duration;
+use embassy_futures::select::{select3, Either3};
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex, channel::Channel, mutex::Mutex, pubsub::PubSubChannel,
+};
+use embassy_time::{Duration, Instant, Ticker};
 use serde::{Deserialize, Serialize};
+use shared::{device_to_host::DeviceToHostMsg, side::KeyboardSide};
 
-use crate::{flash, keys::KEY_EVENTS, utils};
+use crate::{
+    flash,
+    keys::{
+        switches::{self, Finger, LAYOUT_COLS, LAYOUT_ROWS},
+        CURRENT_LAYER, KEY_EVENTS, NUM_LAYERS,
+    },
+    messages::{distributors::MessageProvenance, send_to_host},
+    side,
+    sync::Watch,
+    utils,
+};
 
 static CURRENT_METRICS: Mutex<ThreadModeRawMutex, Metrics> = Mutex::new(Metrics::default());
 
+static KEY_COUNTS: Mutex<ThreadModeRawMutex, KeyCounts> = Mutex::new(KeyCounts::new());
+
 pub static METRIC_UPDATES: PubSubChannel<ThreadModeRawMutex, Metrics, 1, 4, 1> =
     PubSubChannel::new();
 
+static CHORD_TRIGGERS: Channel<ThreadModeRawMutex, usize, 4> = Channel::new();
+
+static LED_POWER: Watch<LedPower> = Watch::new(LedPower::new());
+
+/// Days of active typing time kept
+pub const ACTIVE_DAYS: usize = 7;
+
+/// Chords with an index past this are only counted in the total
+pub const MAX_TRACKED_CHORDS: usize = 16;
+
+/// Presses further apart than this aren't part of the same burst of typing
+const ACTIVE_GAP: Duration = Duration::from_secs(5);
+
+/// How far back words per minute looks, in seconds
+const WPM_WINDOW: usize = 60;
+
+/// Keypresses in a word, the usual convention for measuring WPM
+const CHARS_PER_WORD: u16 = 5;
+
+const DAY: Duration = Duration::from_secs(60 * 60 * 24);
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Metrics {
     #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
     pub keys_pressed: Wrapping<usize>,
+
+    /// Words per minute over the last minute, not saved
+    #[serde(skip)]
+    pub wpm: u16,
+
+    /// Saved on its own so that metrics saved before these existed still load
+    #[serde(skip)]
+    pub typing: TypingStats,
+
+    /// Not saved
+    #[serde(skip)]
+    pub led_power: LedPower,
 }
 
 impl Metrics {
     const fn default() -> Self {
         Self {
             keys_pressed: Wrapping(0),
+            wpm: 0,
+            typing: TypingStats::new(),
+            led_power: LedPower::new(),
+        }
+    }
+}
+
+/// What the LED power limiter is doing, see [`crate::rgb::power`]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LedPower {
+    /// Estimated current the LEDs would draw without limiting, in milliamps
+    pub estimate_ma: u16,
+
+    /// How much the LEDs are scaled to fit the budget, 100 when they aren't
+    pub scale_percent: u8,
+}
+
+impl LedPower {
+    const fn new() -> Self {
+        Self {
+            estimate_ma: 0,
+            scale_percent: 100,
+        }
+    }
+}
+
+impl Default for LedPower {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TypingStats {
+    /// Seconds spent typing, today first then the days before it
+    ///
+    /// There's no clock, so a day is 24 hours of the keyboard being on.
+    pub active_secs: [u32; ACTIVE_DAYS],
+
+    /// Pairs of presses in a row while typing
+    pub bigrams: u32,
+
+    /// Bigrams where both keys are different but pressed by the same finger
+    pub same_finger_bigrams: u32,
+
+    /// Chords fired by this side's chording engine
+    pub chords_triggered: u32,
+
+    /// The same, by chord index
+    pub chord_counts: [u32; MAX_TRACKED_CHORDS],
+}
+
+impl TypingStats {
+    const fn new() -> Self {
+        Self {
+            active_secs: [0; ACTIVE_DAYS],
+            bigrams: 0,
+            same_finger_bigrams: 0,
+            chords_triggered: 0,
+            chord_counts: [0; MAX_TRACKED_CHORDS],
+        }
+    }
+}
+
+impl Default for TypingStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What we need to remember between presses to work out the typing stats
+struct TypingTracker {
+    last_press: Option<(Instant, (u8, u8))>,
+    active_ms: u64,
+    day_started: Instant,
+
+    /// Presses in each of the last [`WPM_WINDOW`] seconds
+    recent: [u16; WPM_WINDOW],
+    second: usize,
+}
+
+impl TypingTracker {
+    fn new() -> Self {
+        Self {
+            last_press: None,
+            active_ms: 0,
+            day_started: Instant::now(),
+            recent: [0; WPM_WINDOW],
+            second: 0,
+        }
+    }
+
+    /// `pos` is the keymap (column, row) pressed
+    fn press(&mut self, m: &mut Metrics, pos: (u8, u8)) {
+        let now = Instant::now();
+
+        self.recent[self.second] = self.recent[self.second].saturating_add(1);
+
+        if let Some((at, last)) = self.last_press {
+            let gap = now - at;
+
+            if gap < ACTIVE_GAP {
+                self.active_ms += gap.as_millis();
+
+                let secs = (self.active_ms / 1000) as u32;
+                self.active_ms %= 1000;
+                m.typing.active_secs[0] = m.typing.active_secs[0].wrapping_add(secs);
+
+                m.typing.bigrams = m.typing.bigrams.wrapping_add(1);
+
+                let same_finger = match (switches::finger(last), switches::finger(pos)) {
+                    (Some(a), Some(b)) => a == b && a.1 != Finger::Thumb && last != pos,
+                    _ => false,
+                };
+
+                if same_finger {
+                    m.typing.same_finger_bigrams = m.typing.same_finger_bigrams.wrapping_add(1);
+                }
+            }
         }
+
+        self.last_press = Some((now, pos));
+    }
+
+    /// Called every second, moves the WPM window along and rolls over the day
+    fn tick(&mut self, m: &mut Metrics) {
+        self.second = (self.second + 1) % WPM_WINDOW;
+        self.recent[self.second] = 0;
+
+        m.wpm = self.recent.iter().sum::<u16>() / CHARS_PER_WORD;
+
+        if self.day_started.elapsed() >= DAY {
+            self.day_started += DAY;
+            m.typing.active_secs.rotate_right(1);
+            m.typing.active_secs[0] = 0;
+        }
+    }
+}
+
+/// Update the LED power figures, picked up on the next metrics tick
+pub fn report_led_power(power: LedPower) {
+    LED_POWER.set(power);
+}
+
+/// Note that a chord fired, called by the chording engine's task
+pub fn chord_triggered(idx: usize) {
+    // losing a count isn't worth blocking key processing for
+    let _ = CHORD_TRIGGERS.try_send(idx);
+}
+
+type KeyCountRow = [u32; LAYOUT_COLS];
+
+/// Flash key for the counts of one row of one layer, rows are saved
+/// separately so a sync only writes the rows that changed
+#[derive(Serialize)]
+struct KeyCountKey {
+    layer: u8,
+    row: u8,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+struct SideCounts {
+    left: u32,
+    right: u32,
+}
+
+/// Presses of each key in the keymap on each layer, only counted on the master
+/// side as it's the only one that knows the current layer
+struct KeyCounts {
+    presses: [[KeyCountRow; LAYOUT_ROWS]; NUM_LAYERS],
+    dirty: [[bool; LAYOUT_ROWS]; NUM_LAYERS],
+    sides: SideCounts,
+    sides_dirty: bool,
+}
+
+impl KeyCounts {
+    const fn new() -> Self {
+        Self {
+            presses: [[[0; LAYOUT_COLS]; LAYOUT_ROWS]; NUM_LAYERS],
+            dirty: [[false; LAYOUT_ROWS]; NUM_LAYERS],
+            sides: SideCounts { left: 0, right: 0 },
+            sides_dirty: false,
+        }
+    }
+
+    async fn load(&mut self) {
+        for (layer, rows) in self.presses.iter_mut().enumerate() {
+            for (row, counts) in rows.iter_mut().enumerate() {
+                let key = KeyCountKey {
+                    layer: layer as u8,
+                    row: row as u8,
+                };
+
+                if let Some(c) = flash::get_keyed::<_, KeyCountRow>(key).await {
+                    *counts = c;
+                }
+            }
+        }
+
+        if let Some(sides) = flash::get::<SideCounts>().await {
+            self.sides = sides;
+        }
+    }
+
+    fn press(&mut self, layer: usize, row: u8, col: u8) {
+        let (row, col) = (row as usize, col as usize);
+
+        if layer < NUM_LAYERS && row < LAYOUT_ROWS && col < LAYOUT_COLS {
+            let count = &mut self.presses[layer][row][col];
+            *count = count.wrapping_add(1);
+            self.dirty[layer][row] = true;
+        }
+
+        match switches::side_of((col as u8, row as u8)) {
+            Some(KeyboardSide::Left) => self.sides.left = self.sides.left.wrapping_add(1),
+            Some(KeyboardSide::Right) => self.sides.right = self.sides.right.wrapping_add(1),
+            None => return,
+        }
+
+        self.sides_dirty = true;
     }
 }
 
@@ -31,8 +301,19 @@ pub async fn init(spawner: &Spawner) {
     crate::log::info!("Initialising metrics");
     if let Some(m) = flash::get::<Metrics>().await {
         utils::log::info!("Loaded up metrics with: {:?}", m);
-        *CURRENT_METRICS.lock().await = m.clone();
-        push_update(m);
+        CURRENT_METRICS.lock().await.keys_pressed = m.keys_pressed;
+    }
+
+    if let Some(t) = flash::get::<TypingStats>().await {
+        utils::log::info!("Loaded up typing stats with: {:?}", t);
+        CURRENT_METRICS.lock().await.typing = t;
+    }
+
+    push_update(CURRENT_METRICS.lock().await.clone());
+
+    if side::is_master() {
+        KEY_COUNTS.lock().await.load().await;
+        spawner.must_spawn(key_press_counter());
     }
 
     spawner.must_spawn(metrics_syncer());
@@ -52,22 +333,122 @@ pub async fn request_sync() {
 #[embassy_executor::task]
 async fn key_counter() {
     let mut sub = KEY_EVENTS.subscriber().unwrap();
+    let mut tracker = TypingTracker::new();
+    let mut ticker = Ticker::every(Duration::from_secs(1));
 
     loop {
-        let k = sub.next_message_pure().await;
-        if !k.is_press() {
-            continue;
-        }
+        let evt = select3(
+            sub.next_message_pure(),
+            CHORD_TRIGGERS.receive(),
+            ticker.next(),
+        )
+        .await;
 
         let mut m = CURRENT_METRICS.lock().await;
-        m.keys_pressed += 1;
+
+        match evt {
+            Either3::First(k) => {
+                if !k.is_press() {
+                    continue;
+                }
+
+                let (row, col) = k.coord();
+
+                m.keys_pressed += 1;
+                tracker.press(&mut m, (col, row));
+            }
+            Either3::Second(idx) => {
+                m.typing.chords_triggered = m.typing.chords_triggered.wrapping_add(1);
+
+                if let Some(c) = m.typing.chord_counts.get_mut(idx) {
+                    *c = c.wrapping_add(1);
+                }
+            }
+            Either3::Third(()) => {
+                let (wpm, led_power) = (m.wpm, m.led_power);
+                tracker.tick(&mut m);
+                m.led_power = LED_POWER.current();
+
+                if m.wpm == wpm && m.led_power == led_power {
+                    continue;
+                }
+            }
+        }
 
         push_update(m.clone());
     }
 }
 
+#[embassy_executor::task]
+async fn key_press_counter() {
+    let mut sub = KEY_EVENTS.subscriber().unwrap();
+
+    loop {
+        let evt = sub.next_message_pure().await;
+        if !evt.is_press() {
+            continue;
+        }
+
+        let (row, col) = evt.coord();
+        let layer = CURRENT_LAYER.current() as usize;
+
+        KEY_COUNTS.lock().await.press(layer, row, col);
+    }
+}
+
+/// Send the press counts of every key to the host
+pub async fn send_key_counts() {
+    let (presses, sides) = {
+        let counts = KEY_COUNTS.lock().await;
+        (counts.presses, counts.sides)
+    };
+
+    for (layer, rows) in presses.iter().enumerate() {
+        for (row, counts) in rows.iter().enumerate() {
+            let msg = DeviceToHostMsg::KeyCounts {
+                layer: layer as u8,
+                row: row as u8,
+                counts: *counts,
+            };
+            send_to_host(msg, MessageProvenance::Origin).await;
+        }
+    }
+
+    let msg = DeviceToHostMsg::SideKeyCounts {
+        left: sides.left,
+        right: sides.right,
+    };
+    send_to_host(msg, MessageProvenance::Origin).await;
+}
+
+/// Write out the key count rows that changed since the last sync
+async fn sync_key_counts() {
+    let (presses, dirty, sides) = {
+        let mut counts = KEY_COUNTS.lock().await;
+        let dirty = core::mem::take(&mut counts.dirty);
+        let sides = core::mem::take(&mut counts.sides_dirty).then_some(counts.sides);
+        (counts.presses, dirty, sides)
+    };
+
+    for (layer, rows) in dirty.iter().enumerate() {
+        for (row, _) in rows.iter().enumerate().filter(|(_, d)| **d) {
+            let key = KeyCountKey {
+                layer: layer as u8,
+                row: row as u8,
+            };
+            let _ = flash::set_keyed(key, &presses[layer][row]).await;
+        }
+    }
+
+    if let Some(sides) = sides {
+        let _ = flash::set(&sides).await;
+    }
+}
+
 #[embassy_executor::task]
 async fn metrics_syncer() {
+    // this limits how often we write to flash, everything gets saved at most
+    // once per tick
     let mut tick = embassy_time::Ticker::every(Duration::from_secs(60 * 5));
     let mut last = Metrics::default();
 
@@ -76,12 +457,20 @@ async fn metrics_syncer() {
 
         let current = CURRENT_METRICS.lock().await.clone();
 
-        if current != last {
+        if current.keys_pressed != last.keys_pressed {
             let _ = flash::set(&current).await;
 
             utils::log::info!("Synced metrics: {:?}", current);
         }
 
+        if current.typing != last.typing {
+            let _ = flash::set(&current.typing).await;
+        }
+
         last = current;
+
+        if side::is_master() {
+            sync_key_counts().await;
+        }
     }
 }
@@ -14,6 +14,9 @@ pub trait Animation {
     fn tick(&mut self);
     fn render(&self, light: &Light) -> ColorRGB;
 
+    /// A key was pressed at `location` (see [`Light::location`]), on either side
+    fn keypress(&mut self, _location: (i16, i16)) {}
+
     fn construct_sync(&self) -> Self::SyncMessage;
     fn sync(&mut self, sync: Self::SyncMessage);
     fn new_from_sync(sync: Self::SyncMessage) -> Self;
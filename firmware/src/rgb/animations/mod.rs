@@ -1,7 +1,8 @@
 use cichlid::ColorRGB;
 use embassy_time::Duration;
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
+use shared::rgb::{AnimationChoice, PerlinColour, PlaylistWeights, Rgb, RippleParams};
 
 use crate::rng::MyRng;
 
@@ -10,12 +11,14 @@ use super::{animation::Animation, layout::Light};
 pub mod null;
 pub mod perlin;
 pub mod rain;
+pub mod ripple;
 pub mod snow;
 
 pub enum DynAnimation {
     Snow(snow::Snow),
     Perlin(perlin::Perlin),
     Rain(rain::Rain),
+    Ripple(ripple::Ripple),
     Null(null::Null),
 }
 
@@ -25,9 +28,55 @@ impl DynAnimation {
             || DynAnimation::Snow(snow::Snow::default()),
             || DynAnimation::Perlin(perlin::Perlin::default()),
             || DynAnimation::Rain(rain::Rain::default()),
+            || DynAnimation::Ripple(ripple::Ripple::default()),
         ];
         OPTS.choose(&mut MyRng).unwrap()()
     }
+
+    pub fn from_choice(choice: AnimationChoice) -> Self {
+        match choice {
+            AnimationChoice::Off => DynAnimation::Null(null::Null),
+            AnimationChoice::Snow => DynAnimation::Snow(snow::Snow::default()),
+            AnimationChoice::Perlin(colour) => {
+                DynAnimation::Perlin(perlin::Perlin::new_from_sync((colour.into(), MyRng.gen())))
+            }
+            AnimationChoice::Rain(colour) => {
+                DynAnimation::Rain(rain::Rain::new_from_sync(colour.map(Into::into)))
+            }
+            AnimationChoice::Ripple(params) => {
+                DynAnimation::Ripple(ripple::Ripple::new_from_sync(params.into()))
+            }
+        }
+    }
+
+    /// Pick an animation with random parameters, weighted by `weights`
+    pub fn from_playlist(weights: PlaylistWeights) -> Option<Self> {
+        let opts: [(u8, fn() -> DynAnimation); 4] = [
+            (weights.snow, || DynAnimation::Snow(snow::Snow::default())),
+            (weights.perlin, || {
+                DynAnimation::Perlin(perlin::Perlin::default())
+            }),
+            (weights.rain, || DynAnimation::Rain(rain::Rain::default())),
+            (weights.ripple, || {
+                DynAnimation::Ripple(ripple::Ripple::default())
+            }),
+        ];
+
+        let total: u32 = opts.iter().map(|(w, _)| *w as u32).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut n = MyRng.gen_range(0..total);
+        for (w, f) in opts {
+            if n < w as u32 {
+                return Some(f());
+            }
+            n -= w as u32;
+        }
+
+        None
+    }
 }
 
 macro_rules! dyn_impl {
@@ -67,6 +116,14 @@ macro_rules! dyn_impl {
                 }
             }
 
+            fn keypress(&mut self, location: (i16, i16)) {
+                match self {
+                    $(
+                        Self::$variant(x) => x.keypress(location)
+                    ),+
+                }
+            }
+
             fn sync(&mut self, sync: Self::SyncMessage) {
                 #[allow(unreachable_patterns)]
                 match (self, sync) {
@@ -112,6 +169,7 @@ dyn_impl!(
     [Snow, snow::Snow],
     [Perlin, perlin::Perlin],
     [Rain, rain::Rain],
+    [Ripple, ripple::Ripple],
     [Null, null::Null]
 );
 
@@ -140,6 +198,10 @@ pub enum AnimationSync {
     Rain(
         #[cfg_attr(feature = "probe", defmt(Debug2Format))] <rain::Rain as Animation>::SyncMessage,
     ),
+    Ripple(
+        #[cfg_attr(feature = "probe", defmt(Debug2Format))]
+        <ripple::Ripple as Animation>::SyncMessage,
+    ),
 }
 
 trait WrapAnimationSync {
@@ -160,6 +222,7 @@ wrap_sync!(snow::Snow, AnimationSync::Snow);
 wrap_sync!(perlin::Perlin, AnimationSync::Perlin);
 wrap_sync!(null::Null, AnimationSync::Null);
 wrap_sync!(rain::Rain, AnimationSync::Rain);
+wrap_sync!(ripple::Ripple, AnimationSync::Ripple);
 
 #[derive(
     serde::Serialize,
@@ -184,6 +247,32 @@ impl From<cichlid::ColorRGB> for ColorRGBWire {
     }
 }
 
+impl From<Rgb> for ColorRGBWire {
+    fn from(Rgb { r, g, b }: Rgb) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl From<RippleParams> for ripple::RippleConfig {
+    fn from(params: RippleParams) -> Self {
+        Self {
+            colour: params.colour.map(Into::into),
+            speed: params.speed,
+            decay_ms: params.decay_ms,
+        }
+    }
+}
+
+impl From<PerlinColour> for perlin::ColourMode {
+    fn from(colour: PerlinColour) -> Self {
+        match colour {
+            PerlinColour::Random => Self::Random,
+            PerlinColour::Single(a) => Self::Single(a.into()),
+            PerlinColour::Double(a, b) => Self::Double(a.into(), b.into()),
+        }
+    }
+}
+
 impl From<ColorRGBWire> for cichlid::ColorRGB {
     fn from(ColorRGBWire { r, g, b }: ColorRGBWire) -> Self {
         cichlid::ColorRGB { r, g, b }
@@ -0,0 +1,143 @@
+use cichlid::ColorRGB;
+use embassy_time::{Duration, Instant};
+use fixed::types::U0F16;
+use micromath::F32Ext;
+use shared::rgb::RippleParams;
+
+use crate::rgb::{
+    animation::Animation,
+    layout::Light,
+    math_utils::{ease_fade, overlay, rand_rainbow},
+};
+
+use super::ColorRGBWire;
+
+/// Width of the lit band of a ring (mm)
+const RING_WIDTH: f32 = 25.0;
+
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Hash,
+    Eq,
+    PartialEq,
+    postcard::experimental::max_size::MaxSize,
+)]
+pub struct RippleConfig {
+    pub colour: Option<ColorRGBWire>,
+    /// mm per second
+    pub speed: u16,
+    pub decay_ms: u16,
+}
+
+struct Ring {
+    x: f32,
+    y: f32,
+    started: Instant,
+    colour: ColorRGB,
+}
+
+/// Rings spreading out from each key press
+///
+/// Both sides are told about every key press, and the ring is placed using
+/// the location of the key across both boards, so a press on one side ripples
+/// over onto the other.
+pub struct Ripple {
+    config: RippleConfig,
+    now: Instant,
+    rings: heapless::Deque<Ring, 8>,
+}
+
+impl Default for Ripple {
+    fn default() -> Self {
+        Self::new_from_sync(RippleParams::DEFAULT.into())
+    }
+}
+
+impl Animation for Ripple {
+    type SyncMessage = RippleConfig;
+
+    fn tick_rate(&self) -> Duration {
+        Duration::from_hz(60)
+    }
+
+    fn tick(&mut self) {
+        self.now = Instant::now();
+
+        let decay = Duration::from_millis(self.config.decay_ms as u64);
+        while self
+            .rings
+            .front()
+            .is_some_and(|r| self.now.saturating_duration_since(r.started) > decay)
+        {
+            let _ = self.rings.pop_front();
+        }
+    }
+
+    fn render(&self, light: &Light) -> ColorRGB {
+        let xx = light.location.0 as f32;
+        let yy = light.location.1 as f32;
+        let decay = self.config.decay_ms.max(1) as f32;
+
+        let mut out = ColorRGB::Black;
+
+        for ring in self.rings.iter() {
+            let age = self.now.saturating_duration_since(ring.started).as_millis() as f32;
+
+            let radius = self.config.speed as f32 * age / 1000.0;
+
+            let (dx, dy) = (ring.x - xx, ring.y - yy);
+            let dist = (dx * dx + dy * dy).sqrt();
+
+            let b = 1.0 - (dist - radius).abs() / RING_WIDTH;
+            let fade = 1.0 - age / decay;
+            let b = (b * fade).clamp(0.0, 1.0);
+
+            if b == 0.0 {
+                continue;
+            }
+
+            let level = ease_fade(U0F16::saturating_from_num(b));
+
+            let mut colour = ring.colour;
+            colour.scale(level);
+
+            out = overlay(out, colour);
+        }
+
+        out
+    }
+
+    fn keypress(&mut self, (x, y): (i16, i16)) {
+        if self.rings.is_full() {
+            let _ = self.rings.pop_front();
+        }
+
+        let ring = Ring {
+            x: x as f32,
+            y: y as f32,
+            started: Instant::now(),
+            colour: self.config.colour.map_or_else(rand_rainbow, Into::into),
+        };
+        let _ = self.rings.push_back(ring);
+    }
+
+    fn construct_sync(&self) -> Self::SyncMessage {
+        self.config
+    }
+
+    fn sync(&mut self, sync: Self::SyncMessage) {
+        self.config = sync;
+    }
+
+    fn new_from_sync(sync: Self::SyncMessage) -> Self {
+        Self {
+            config: sync,
+            now: Instant::now(),
+            rings: Default::default(),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Overall LED brightness, kept the same on both sides
+//!
+//! The brightness is applied to the final colour of each LED just before gamma
+//! correction, so the dithering in the gamma correction still smooths out low
+//! brightness levels.
+
+use embassy_time::{Duration, Instant};
+use fixed::types::U16F16;
+use serde::{Deserialize, Serialize};
+
+use crate::{flash, interboard, messages::device_to_device::DeviceToDevice, sync::Watch};
+
+pub const MAX_LEVEL: u8 = 255;
+
+/// How much each brightness up/down action changes the level by
+const STEP: u8 = 32;
+
+/// How long the ramp takes to move by one level
+const RAMP_STEP: Duration = Duration::from_millis(2);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+struct Brightness {
+    level: u8,
+    /// The level to go back to when toggled back on
+    on_level: u8,
+}
+
+impl Brightness {
+    const fn with_level(self, level: u8) -> Self {
+        Self {
+            level,
+            on_level: if level == 0 { self.on_level } else { level },
+        }
+    }
+}
+
+static BRIGHTNESS: Watch<Brightness> = Watch::new(Brightness {
+    level: MAX_LEVEL,
+    on_level: MAX_LEVEL,
+});
+
+/// Restore the saved brightness
+pub async fn load() {
+    if let Some(b) = flash::get::<Brightness>().await {
+        BRIGHTNESS.set(b);
+    }
+}
+
+/// The brightness the LEDs are ramping towards
+pub fn target() -> u8 {
+    BRIGHTNESS.current().level
+}
+
+async fn apply(b: Brightness, mirror: bool) {
+    if b == BRIGHTNESS.current() {
+        return;
+    }
+
+    crate::log::info!("Setting brightness to {}", b.level);
+
+    BRIGHTNESS.set(b);
+    flash::set(&b).await;
+
+    if mirror {
+        interboard::send_msg(DeviceToDevice::SetBrightness(b.level), 3).await;
+    }
+}
+
+/// Set the brightness on both sides
+pub async fn set(level: u8) {
+    apply(BRIGHTNESS.current().with_level(level), true).await;
+}
+
+pub async fn up() {
+    set(target().saturating_add(STEP)).await;
+}
+
+pub async fn down() {
+    set(target().saturating_sub(STEP)).await;
+}
+
+/// Turn the LEDs off, or back on to the brightness they were at before
+pub async fn toggle() {
+    let b = BRIGHTNESS.current();
+
+    let level = if b.level == 0 {
+        b.on_level.max(STEP)
+    } else {
+        0
+    };
+
+    set(level).await;
+}
+
+/// Take on the brightness set on the other side
+pub async fn set_from_other_side(level: u8) {
+    apply(BRIGHTNESS.current().with_level(level), false).await;
+}
+
+/// Moves the applied brightness smoothly towards the target
+pub struct Ramp {
+    level: u8,
+    last: Instant,
+}
+
+impl Default for Ramp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ramp {
+    pub fn new() -> Self {
+        Self {
+            level: target(),
+            last: Instant::now(),
+        }
+    }
+
+    /// Step towards the target, returning the factor to scale colours by
+    pub fn update(&mut self) -> U16F16 {
+        let steps = (self.last.elapsed().as_ticks() / RAMP_STEP.as_ticks()).min(255) as u8;
+        if steps > 0 {
+            self.last = Instant::now();
+        }
+
+        let target = target();
+        self.level = if self.level < target {
+            self.level.saturating_add(steps).min(target)
+        } else {
+            self.level.saturating_sub(steps).max(target)
+        };
+
+        U16F16::from_num(self.level) / U16F16::from_num(MAX_LEVEL)
+    }
+}
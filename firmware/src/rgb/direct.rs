@@ -0,0 +1,111 @@
+//! Frames streamed from the host, shown instead of the animation
+//!
+//! The host sends every LED on both sides to the side it's connected to,
+//! which keeps its own LEDs and passes the rest on to the other side. Each
+//! side goes back to its animation once frames stop arriving.
+
+use cichlid::ColorRGB;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant};
+use shared::{
+    host_to_device::{DIRECT_LEDS_PER_SIDE, MAX_DIRECT_LEDS_CHUNK},
+    rgb::Rgb,
+};
+
+use crate::{interboard, messages::device_to_device::DeviceToDevice, side::get_side};
+
+use super::layout::NUM_LEDS;
+
+const _: () = assert!(DIRECT_LEDS_PER_SIDE == NUM_LEDS as usize);
+
+/// Go back to the animation after this long without any frames
+const DIRECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Lower priority than anything else sent to the other side, key presses
+/// shouldn't wait behind LED frames
+const FORWARD_PRIORITY: u8 = 5;
+
+struct DirectFrame {
+    colours: [ColorRGB; NUM_LEDS as usize],
+    last_update: Option<Instant>,
+}
+
+static DIRECT_FRAME: Mutex<ThreadModeRawMutex, DirectFrame> = Mutex::new(DirectFrame {
+    colours: [ColorRGB::Black; NUM_LEDS as usize],
+    last_update: None,
+});
+
+/// Where this side's LEDs start in a direct frame
+fn this_side_offset() -> usize {
+    if get_side().is_left() {
+        0
+    } else {
+        DIRECT_LEDS_PER_SIDE
+    }
+}
+
+async fn store(offset: usize, colours: impl Iterator<Item = Rgb>) {
+    let mut frame = DIRECT_FRAME.lock().await;
+
+    for (dest, Rgb { r, g, b }) in frame.colours.iter_mut().skip(offset).zip(colours) {
+        *dest = ColorRGB::new(r, g, b);
+    }
+
+    frame.last_update = Some(Instant::now());
+
+    // a host driving the LEDs keeps them on
+    super::idle::wake();
+}
+
+/// Take in a chunk of a frame from the host, keeping this side's LEDs and
+/// passing the other side's on
+pub async fn receive_from_host(offset: u8, colours: &[Rgb]) {
+    let ours = this_side_offset()..this_side_offset() + DIRECT_LEDS_PER_SIDE;
+
+    let mut ours_offset = None;
+    let mut theirs = heapless::Vec::<Rgb, MAX_DIRECT_LEDS_CHUNK>::new();
+    let mut theirs_offset = None;
+
+    for (idx, &colour) in (offset as usize..).zip(colours) {
+        if ours.contains(&idx) {
+            ours_offset.get_or_insert(idx - ours.start);
+        } else if idx < DIRECT_LEDS_PER_SIDE * 2 {
+            theirs_offset.get_or_insert(idx);
+            let _ = theirs.push(colour);
+        }
+    }
+
+    if let Some(local) = ours_offset {
+        let skip = (local + ours.start).saturating_sub(offset as usize);
+        store(local, colours.iter().copied().skip(skip)).await;
+    }
+
+    if let Some(offset) = theirs_offset {
+        let msg = DeviceToDevice::DirectLeds {
+            offset: offset as u8,
+            colours: theirs,
+        };
+
+        // a dropped chunk is fixed by the next frame
+        let _ = interboard::try_send_msg(msg, FORWARD_PRIORITY);
+    }
+}
+
+/// Take in our part of a chunk passed on by the other side
+pub async fn receive_from_other_side(offset: u8, colours: &[Rgb]) {
+    let Some(offset) = (offset as usize).checked_sub(this_side_offset()) else {
+        return;
+    };
+
+    store(offset, colours.iter().copied()).await;
+}
+
+/// The frame to show, if the host has sent one recently
+pub(super) async fn frame() -> Option<[ColorRGB; NUM_LEDS as usize]> {
+    let frame = DIRECT_FRAME.lock().await;
+
+    match frame.last_update {
+        Some(t) if t.elapsed() < DIRECT_TIMEOUT => Some(frame.colours),
+        _ => None,
+    }
+}
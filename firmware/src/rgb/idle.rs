@@ -0,0 +1,176 @@
+//! Turning the LEDs off while the keyboard isn't being used
+//!
+//! Key events from both sides pass through [`KEY_EVENTS`] on each side, so
+//! each side works out when it's idle on its own. Once idle the LEDs fade out,
+//! their power is switched off and the runner stops until the next key press,
+//! which fades them back in.
+//!
+//! Frames streamed from the host and notifications count as activity too, so
+//! the LEDs stay on while the host is driving them and come back on to show a
+//! notification.
+
+use embassy_futures::select::{select4, Either4};
+use embassy_time::{Duration, Instant, Timer};
+use fixed::types::U16F16;
+use shared::rgb::LedIdleTimeout;
+
+use crate::{flash, keys::KEY_EVENTS, sync::Watch};
+
+/// How long fading out takes to move by one level, about two seconds in all
+const FADE_OUT_STEP: Duration = Duration::from_millis(8);
+
+/// How long fading back in takes to move by one level, about half a second
+const FADE_IN_STEP: Duration = Duration::from_millis(2);
+
+static TIMEOUT: Watch<LedIdleTimeout> = Watch::new(LedIdleTimeout::DEFAULT);
+
+/// Whether there's been a key press within the timeout
+static AWAKE: Watch<bool> = Watch::new(true);
+
+/// Activity other than key presses keeps the LEDs on until at least this
+static KEEP_AWAKE_UNTIL: Watch<Instant> = Watch::new(Instant::from_ticks(0));
+
+/// Activity other than key presses is only passed on to the watcher when it
+/// moves the time to keep awake until by more than this, direct frames arrive
+/// many times a second
+const KEEP_AWAKE_GRANULARITY: Duration = Duration::from_secs(1);
+
+/// Restore the saved timeout
+pub async fn load() {
+    if let Some(t) = flash::get::<LedIdleTimeout>().await {
+        TIMEOUT.set(t);
+    }
+}
+
+pub async fn set_timeout(timeout: LedIdleTimeout) {
+    crate::log::info!("Setting LED idle timeout to {}s", timeout.seconds);
+
+    TIMEOUT.set(timeout);
+    flash::set(&timeout).await;
+}
+
+/// Wait until a key is pressed after the LEDs have gone idle
+pub async fn wait_for_wake() {
+    AWAKE.wait_for(|&awake| awake).await;
+}
+
+/// Count something other than a key press as activity, waking the LEDs if
+/// they've gone idle
+pub fn wake() {
+    keep_awake_until(Instant::now());
+}
+
+/// Keep the LEDs on until at least `until`, waking them if they've gone idle,
+/// the idle timeout starts from then
+pub fn keep_awake_until(until: Instant) {
+    let asleep = !AWAKE.current();
+
+    if asleep || until > KEEP_AWAKE_UNTIL.current() + KEEP_AWAKE_GRANULARITY {
+        KEEP_AWAKE_UNTIL.set(until.max(KEEP_AWAKE_UNTIL.current()));
+    }
+
+    if asleep {
+        crate::log::debug!("Host activity, waking the LEDs");
+        AWAKE.set(true);
+    }
+}
+
+#[embassy_executor::task]
+pub async fn idle_watcher() {
+    let mut sub = KEY_EVENTS.subscriber().unwrap();
+    let mut last_activity = Instant::now();
+
+    loop {
+        let timeout = TIMEOUT.current();
+        let since = last_activity.max(KEEP_AWAKE_UNTIL.current());
+
+        let go_idle = async move {
+            if timeout.seconds == 0 || !AWAKE.current() {
+                core::future::pending::<()>().await;
+            }
+
+            Timer::at(since + Duration::from_secs(timeout.seconds as u64)).await;
+        };
+
+        match select4(
+            sub.next_message_pure(),
+            go_idle,
+            TIMEOUT.wait(),
+            KEEP_AWAKE_UNTIL.wait(),
+        )
+        .await
+        {
+            Either4::First(evt) => {
+                last_activity = Instant::now();
+
+                if evt.is_press() && !AWAKE.current() {
+                    crate::log::debug!("Key pressed, waking the LEDs");
+                    AWAKE.set(true);
+                }
+            }
+            Either4::Second(()) => {
+                crate::log::debug!("No key presses for {}s, LEDs going idle", timeout.seconds);
+                AWAKE.set(false);
+            }
+            Either4::Third(_) => {
+                // the new timeout starts from now, with the LEDs back on
+                last_activity = Instant::now();
+                AWAKE.set(true);
+            }
+            Either4::Fourth(_) => {
+                // the LEDs were already woken, the timeout just needs to
+                // start from the new time
+            }
+        }
+    }
+}
+
+/// Fades the LEDs out when idle, and back in when woken
+pub struct Fade {
+    level: u8,
+    last: Instant,
+}
+
+impl Default for Fade {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fade {
+    pub fn new() -> Self {
+        Self {
+            level: u8::MAX,
+            last: Instant::now(),
+        }
+    }
+
+    /// Step towards on or off, returning the factor to scale colours by
+    pub fn update(&mut self) -> U16F16 {
+        let awake = AWAKE.current();
+        let step = if awake { FADE_IN_STEP } else { FADE_OUT_STEP };
+
+        let steps = (self.last.elapsed().as_ticks() / step.as_ticks()).min(255) as u8;
+        if steps > 0 {
+            self.last = Instant::now();
+        }
+
+        self.level = if awake {
+            self.level.saturating_add(steps)
+        } else {
+            self.level.saturating_sub(steps)
+        };
+
+        U16F16::from_num(self.level) / U16F16::from_num(u8::MAX)
+    }
+
+    /// Whether the LEDs have finished fading out
+    pub fn is_off(&self) -> bool {
+        self.level == 0 && !AWAKE.current()
+    }
+
+    /// Start fading back in from off, after waking
+    pub fn restart(&mut self) {
+        self.last = Instant::now();
+    }
+}
@@ -0,0 +1,93 @@
+//! Per layer colours for individual keys, drawn by the overlay while their
+//! layer is active
+//!
+//! The defaults are in [`crate::keys::key_colours`], each row can be replaced
+//! by the host and is saved in flash.
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use serde::Serialize;
+
+use crate::{
+    flash,
+    keys::{
+        key_colours::{default_row, KeyColourRow},
+        switches::{LAYOUT_COLS, LAYOUT_ROWS},
+        NUM_LAYERS,
+    },
+    sync::Watch,
+};
+
+pub type KeyColourMap = [KeyColourRow; LAYOUT_ROWS];
+
+pub static KEY_COLOURS: Mutex<ThreadModeRawMutex, [KeyColourMap; NUM_LAYERS]> =
+    Mutex::new([[[None; LAYOUT_COLS]; LAYOUT_ROWS]; NUM_LAYERS]);
+
+/// Bumped whenever the maps change, so the overlay knows to pick them up again
+pub static KEY_COLOURS_VERSION: Watch<u16> = Watch::new(0);
+
+/// Flash key for the colours of one row of one layer
+#[derive(Serialize)]
+struct KeyColourKey {
+    layer: u8,
+    row: u8,
+}
+
+fn changed() {
+    KEY_COLOURS_VERSION.set(KEY_COLOURS_VERSION.current().wrapping_add(1));
+}
+
+/// Load the saved maps, falling back to the defaults for rows that haven't
+/// been set
+pub async fn load() {
+    let mut maps = KEY_COLOURS.lock().await;
+
+    for (layer, rows) in maps.iter_mut().enumerate() {
+        for (row, colours) in rows.iter_mut().enumerate() {
+            let key = KeyColourKey {
+                layer: layer as u8,
+                row: row as u8,
+            };
+
+            *colours = match flash::get_keyed::<_, KeyColourRow>(key).await {
+                Some(c) => c,
+                None => default_row(layer, row),
+            };
+        }
+    }
+
+    drop(maps);
+    changed();
+}
+
+pub async fn set_row(layer: u8, row: u8, colours: KeyColourRow) {
+    let (l, r) = (layer as usize, row as usize);
+    if l >= NUM_LAYERS || r >= LAYOUT_ROWS {
+        crate::log::warn!("No row {} on layer {} to colour", row, layer);
+        return;
+    }
+
+    KEY_COLOURS.lock().await[l][r] = colours;
+    changed();
+
+    let _ = flash::set_keyed(KeyColourKey { layer, row }, &colours).await;
+}
+
+/// Forget the host's colours and go back to the defaults
+pub async fn reset() {
+    let mut maps = KEY_COLOURS.lock().await;
+
+    for (layer, rows) in maps.iter_mut().enumerate() {
+        for (row, colours) in rows.iter_mut().enumerate() {
+            let key = KeyColourKey {
+                layer: layer as u8,
+                row: row as u8,
+            };
+
+            let _ = flash::delete_keyed::<_, KeyColourRow>(key).await;
+            *colours = default_row(layer, row);
+        }
+    }
+
+    drop(maps);
+    changed();
+}
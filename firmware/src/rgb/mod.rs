@@ -1,10 +1,18 @@
 use embassy_executor::Spawner;
-use embassy_nrf::{gpio::AnyPin, peripherals::PWM0};
+use embassy_futures::select::{select, Either};
+use embassy_nrf::{
+    gpio::{AnyPin, Level, Output, OutputDrive},
+    peripherals::PWM0,
+};
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
 use embassy_time::{Duration, Timer};
+use serde::{Deserialize, Serialize};
+use shared::rgb::{AnimationChoice, RandomizerPolicy};
 
 use crate::{
+    flash,
     interboard::{self, THIS_SIDE_MESSAGE_BUS},
+    keys::switches,
     messages::device_to_device::DeviceToDevice,
     side,
 };
@@ -13,24 +21,43 @@ use self::{animation::Animation, animations::DynAnimation};
 
 pub mod animation;
 pub mod animations;
+pub mod brightness;
+pub mod direct;
 mod driver;
+pub mod idle;
+pub mod key_colours;
 pub mod layout;
 pub mod math_utils;
+pub mod notification;
+mod overlay;
+pub mod power;
 mod runner;
 
 pub(super) static RGB_CMD_CHANNEL: Channel<ThreadModeRawMutex, Command, 1> = Channel::new();
 
-pub fn init(spawner: &Spawner, pwm: PWM0, pin: AnyPin) {
+static ANIMATION_CONTROL: Channel<ThreadModeRawMutex, AnimationControl, 2> = Channel::new();
+
+/// Locations of key presses on both sides, passed on to the animations
+pub(super) static KEYPRESS_LOCATIONS: Channel<ThreadModeRawMutex, (i16, i16), 8> = Channel::new();
+
+/// Tell the animations about a key press, dropped if they're behind
+fn keypress_at(location: (i16, i16)) {
+    let _ = KEYPRESS_LOCATIONS.try_send(location);
+}
+
+pub fn init(spawner: &Spawner, pwm: PWM0, pin: AnyPin, power_pin: AnyPin) {
     crate::log::info!("Initialising RGB");
     let d = driver::Ws2812::new(pwm, pin);
+    let power = Output::new(power_pin, Level::High, OutputDrive::HighDrive);
 
-    spawner.must_spawn(runner::rgb_runner(d));
+    spawner.must_spawn(runner::rgb_runner(d, power));
+    spawner.must_spawn(idle::idle_watcher());
     spawner.must_spawn(runner::apply_keypresses());
     spawner.must_spawn(runner::sparkle_ticker());
     spawner.must_spawn(command_listener());
 
     if side::is_master() {
-        spawner.must_spawn(animation_randomizer());
+        spawner.must_spawn(animation_selector());
     }
 }
 
@@ -42,6 +69,26 @@ async fn command_listener() {
         let cmd = match sub.next_message_pure().await {
             DeviceToDevice::SetAnimation(a) => Command::SetNextAnimation(a),
             DeviceToDevice::SyncAnimation(a) => Command::SyncAnimation(a),
+            // presses on this side are picked up from the matrix in the runner,
+            // key events are (row, column) but switches are found by (column, row)
+            DeviceToDevice::KeyPress(row, col) => {
+                if let Some(location) = switches::location_of((col, row)) {
+                    keypress_at(location);
+                }
+                continue;
+            }
+            DeviceToDevice::DirectLeds { offset, colours } => {
+                direct::receive_from_other_side(offset, &colours).await;
+                continue;
+            }
+            DeviceToDevice::Notify(n) => {
+                notification::show_from_other_side(n);
+                continue;
+            }
+            DeviceToDevice::SetBrightness(level) => {
+                brightness::set_from_other_side(level).await;
+                continue;
+            }
             _ => continue,
         };
 
@@ -49,16 +96,91 @@ async fn command_listener() {
     }
 }
 
+/// Changes to which animation is shown, only handled on the master side
+pub enum AnimationControl {
+    Set(AnimationChoice),
+    /// Move on to the next animation, with default parameters
+    Next,
+    SetPolicy(RandomizerPolicy),
+}
+
+pub async fn control_animation(ctl: AnimationControl) {
+    ANIMATION_CONTROL.send(ctl).await;
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedAnimation(AnimationChoice);
+
+/// Start showing an animation on both sides
+async fn show(anim: DynAnimation) {
+    let sync = anim.construct_sync();
+
+    send_cmd(Command::SetNextAnimation(sync.clone())).await;
+    interboard::send_msg(DeviceToDevice::SetAnimation(sync), 3).await;
+}
+
+async fn choose(choice: AnimationChoice) {
+    crate::log::info!("Choosing animation: {}", choice);
+
+    flash::set(&SavedAnimation(choice)).await;
+    show(DynAnimation::from_choice(choice)).await;
+}
+
 #[embassy_executor::task]
-async fn animation_randomizer() {
+async fn animation_selector() {
+    let mut choice = flash::get::<SavedAnimation>().await.map(|s| s.0);
+    let mut policy = flash::get::<RandomizerPolicy>().await.unwrap_or_default();
+
+    match choice {
+        Some(c) => show(DynAnimation::from_choice(c)).await,
+        None => show(DynAnimation::random()).await,
+    }
+
     loop {
-        Timer::after(Duration::from_secs(60 * 5)).await;
+        let minutes = match policy {
+            RandomizerPolicy::Off => None,
+            RandomizerPolicy::Interval { minutes } | RandomizerPolicy::Playlist { minutes, .. } => {
+                Some(minutes.max(1))
+            }
+        };
+
+        let randomize = async {
+            match minutes {
+                Some(m) => Timer::after(Duration::from_secs(m as u64 * 60)).await,
+                None => core::future::pending().await,
+            }
+        };
+
+        match select(ANIMATION_CONTROL.receive(), randomize).await {
+            Either::First(AnimationControl::Set(c)) => {
+                choice = Some(c);
+                choose(c).await;
+            }
+            Either::First(AnimationControl::Next) => {
+                let c = choice.unwrap_or(AnimationChoice::Off).next();
+                choice = Some(c);
+                choose(c).await;
+            }
+            Either::First(AnimationControl::SetPolicy(p)) => {
+                crate::log::info!("Setting animation randomizer policy: {}", p);
 
-        let anim = DynAnimation::random();
-        let sync = anim.construct_sync();
+                policy = p;
+                flash::set(&p).await;
+            }
+            Either::Second(()) => {
+                let anim = match policy {
+                    RandomizerPolicy::Off => None,
+                    RandomizerPolicy::Interval { .. } => Some(DynAnimation::random()),
+                    RandomizerPolicy::Playlist { weights, .. } => {
+                        DynAnimation::from_playlist(weights)
+                    }
+                };
 
-        send_cmd(Command::SetNextAnimation(sync.clone())).await;
-        interboard::send_msg(DeviceToDevice::SetAnimation(sync), 3).await;
+                if let Some(anim) = anim {
+                    show(anim).await;
+                }
+            }
+        }
     }
 }
 
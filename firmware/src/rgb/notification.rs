@@ -0,0 +1,138 @@
+//! Effects from the host shown over the animation for a while, kept the same
+//! on both sides
+//!
+//! A notification is drawn over everything else, but it doesn't touch the
+//! running animation, which carries on underneath and is seen again as soon as
+//! the notification ends.
+
+use cichlid::ColorRGB;
+use embassy_time::{Duration, Instant};
+use fixed::types::U0F16;
+use shared::rgb::{Notification, NotificationEffect};
+
+use crate::{
+    interboard,
+    keys::switches::{self, LAYOUT_COLS},
+    messages::device_to_device::DeviceToDevice,
+    sync::Watch,
+};
+
+use super::{
+    idle,
+    math_utils::{blend, ease_fade},
+};
+
+/// Keymap row the progress bar is drawn along
+const PROGRESS_ROW: u8 = 1;
+
+/// How much the unfilled part of the progress bar is dimmed
+const PROGRESS_BACKGROUND_DIM: u8 = 200;
+
+/// How long a flash spends on and then off
+const FLASH_PERIOD: Duration = Duration::from_millis(500);
+
+/// How long a pulse takes to fade in and back out
+const PULSE_PERIOD: Duration = Duration::from_millis(1500);
+
+/// The notification being shown and when it started
+static NOTIFICATION: Watch<Option<(Notification, Instant)>> = Watch::new(None);
+
+fn apply(notification: Notification) {
+    crate::log::info!("Showing notification for {}s", notification.seconds);
+
+    let current = (notification.seconds > 0).then(|| (notification, Instant::now()));
+
+    // notifications are still shown when the LEDs have gone idle, and keep
+    // them on for as long as they last
+    if let Some((n, started)) = current {
+        idle::keep_awake_until(started + Duration::from_secs(n.seconds as u64));
+    }
+
+    NOTIFICATION.set(current);
+}
+
+/// Show a notification on both sides
+pub async fn show(notification: Notification) {
+    apply(notification);
+
+    interboard::send_msg(DeviceToDevice::Notify(notification), 3).await;
+}
+
+/// Show a notification sent from the other side
+pub fn show_from_other_side(notification: Notification) {
+    apply(notification);
+}
+
+/// How far through a `period` long cycle we are at `elapsed`
+fn phase(elapsed: Duration, period: Duration) -> U0F16 {
+    let ticks = elapsed.as_ticks() % period.as_ticks();
+    U0F16::saturating_from_num(ticks as f32 / period.as_ticks() as f32)
+}
+
+pub struct NotificationOverlay {
+    current: Option<(Notification, Instant)>,
+}
+
+impl Default for NotificationOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NotificationOverlay {
+    pub fn new() -> Self {
+        Self {
+            current: NOTIFICATION.current(),
+        }
+    }
+
+    /// Pick up the latest notification, called once per frame
+    pub fn update(&mut self) {
+        self.current = NOTIFICATION
+            .current()
+            .filter(|(n, started)| started.elapsed() < Duration::from_secs(n.seconds as u64));
+    }
+
+    /// Draw the notification over the colour of the LED at `idx`
+    pub fn apply(&self, idx: usize, base: ColorRGB) -> ColorRGB {
+        let Some((notification, started)) = self.current else {
+            return base;
+        };
+
+        let colour = ColorRGB::new(
+            notification.colour.r,
+            notification.colour.g,
+            notification.colour.b,
+        );
+
+        match notification.effect {
+            NotificationEffect::Flash => {
+                let on = started.elapsed().as_ticks() / FLASH_PERIOD.as_ticks() % 2 == 0;
+                if on {
+                    colour
+                } else {
+                    base
+                }
+            }
+            NotificationEffect::Pulse => {
+                // fade in over the first half of the period and out over the second
+                let p = phase(started.elapsed(), PULSE_PERIOD).to_num::<f32>();
+                let up_down = U0F16::saturating_from_num(1.0 - (2.0 * p - 1.0).abs());
+                blend(base, colour, ease_fade(up_down))
+            }
+            NotificationEffect::Progress { percent } => {
+                let (col, row) = switches::this_side().switches[idx].logical;
+                if row != PROGRESS_ROW {
+                    return base;
+                }
+
+                // how far into this key the bar reaches, in 256ths of a key
+                let filled = percent.min(100) as i32 * LAYOUT_COLS as i32 * 256 / 100;
+                let amount = (filled - col as i32 * 256).clamp(0, 255) as u8;
+
+                let background = blend(base, ColorRGB::Black, PROGRESS_BACKGROUND_DIM);
+                blend(background, colour, amount)
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Keyboard state drawn over the top of the running animation
+//!
+//! - Keys bound on the active layer are highlighted in the layer's colour,
+//!   with a fainter wash over the rest of the board
+//! - Keys with a colour in the active layer's key colour map are drawn in it,
+//!   see [`super::key_colours`]
+//! - The outer column of the left side shows held ctrl, shift, alt and gui
+//! - The outer column of the right side shows caps lock, caps word, and
+//!   briefly which host key presses are going to after it changes
+//! - The thumb clusters are tinted while one-shots are active
+
+use cichlid::ColorRGB;
+use embassy_time::{Duration, Instant};
+use keyberon::action::Action;
+
+use crate::keys::{
+    indicators::{Indicators, INDICATORS},
+    layout::LAYERS,
+    oneshot::{OneShotState, ONE_SHOT_STATE},
+    switches, CURRENT_LAYER,
+};
+
+use super::{
+    key_colours::{KEY_COLOURS, KEY_COLOURS_VERSION},
+    layout::{self, Lights, NUM_LEDS},
+    math_utils::blend,
+};
+
+/// Colour of each layer, the base layer isn't drawn
+const LAYER_COLOURS: [ColorRGB; crate::keys::NUM_LAYERS] = [
+    ColorRGB::Black,
+    ColorRGB::new(0, 90, 255),
+    ColorRGB::new(40, 255, 60),
+];
+
+/// How strongly keys bound on the active layer are highlighted, and how
+/// strongly everything else is washed in the layer's colour
+const LAYER_HIGHLIGHT: u8 = 200;
+const LAYER_WASH: u8 = 60;
+
+const MOD_COLOUR: ColorRGB = ColorRGB::new(255, 0, 200);
+
+/// Keymap position of the ctrl, shift, alt and gui indicators
+const MOD_INDICATORS: [(u8, u8); 4] = [(0, 1), (0, 2), (0, 3), (0, 4)];
+
+const CAPS_LOCK_COLOUR: ColorRGB = ColorRGB::new(255, 255, 255);
+const CAPS_LOCK_INDICATOR: (u8, u8) = (11, 1);
+
+const CAPS_WORD_COLOUR: ColorRGB = ColorRGB::new(255, 200, 0);
+const CAPS_WORD_INDICATOR: (u8, u8) = (11, 2);
+
+const USB_HOST_COLOUR: ColorRGB = ColorRGB::new(255, 255, 255);
+const HOST_INDICATOR: (u8, u8) = (11, 0);
+
+/// How long to show the host indicator for after the host changes
+const HOST_INDICATOR_DURATION: Duration = Duration::from_secs(3);
+
+const ONE_SHOT_COLOUR: ColorRGB = ColorRGB::new(255, 140, 0);
+
+/// A colour for each BLE host, so switching between them is visible
+fn host_colour(addr: [u8; 6]) -> ColorRGB {
+    let hash = addr.iter().fold(0u8, |h, &b| h.rotate_left(3) ^ b);
+    super::math_utils::rainbow(hash as f32 / 255.0)
+}
+
+fn is_bound(layer: u8, (col, row): (u8, u8)) -> bool {
+    !matches!(
+        LAYERS[layer as usize][row as usize][col as usize],
+        Action::Trans | Action::NoOp
+    )
+}
+
+pub struct Overlay {
+    lights: &'static Lights,
+    layer: u8,
+    indicators: Indicators,
+    one_shot: OneShotState,
+    host_changed: Instant,
+
+    /// The active layer's key colours by light, and the layer and map version
+    /// they came from
+    key_colours: [Option<ColorRGB>; NUM_LEDS as usize],
+    key_colours_from: Option<(u8, u16)>,
+}
+
+impl Overlay {
+    pub fn new(lights: &'static Lights) -> Self {
+        Self {
+            lights,
+            layer: CURRENT_LAYER.current(),
+            indicators: INDICATORS.current(),
+            one_shot: ONE_SHOT_STATE.current(),
+            host_changed: Instant::now(),
+            key_colours: [None; NUM_LEDS as usize],
+            key_colours_from: None,
+        }
+    }
+
+    /// Pick up the latest keyboard state, called once per frame
+    pub async fn update(&mut self) {
+        let indicators = INDICATORS.current();
+        if indicators.ble_host != self.indicators.ble_host {
+            self.host_changed = Instant::now();
+        }
+
+        self.layer = CURRENT_LAYER.current();
+        self.indicators = indicators;
+        self.one_shot = ONE_SHOT_STATE.current();
+
+        let from = (self.layer, KEY_COLOURS_VERSION.current());
+        if self.key_colours_from != Some(from) {
+            self.key_colours_from = Some(from);
+            self.load_key_colours().await;
+        }
+    }
+
+    async fn load_key_colours(&mut self) {
+        self.key_colours = [None; NUM_LEDS as usize];
+
+        let maps = KEY_COLOURS.lock().await;
+        let Some(map) = maps.get(self.layer as usize) else {
+            return;
+        };
+
+        for (row, colours) in map.iter().enumerate() {
+            for (col, colour) in colours.iter().enumerate() {
+                if let (Some(colour), Some(idx)) = (colour, self.lights.logical_index[col][row]) {
+                    self.key_colours[idx as usize] =
+                        Some(ColorRGB::new(colour.r, colour.g, colour.b));
+                }
+            }
+        }
+    }
+
+    /// Draw the overlay over the colour of the LED at `idx`
+    pub fn apply(&self, idx: usize, base: ColorRGB) -> ColorRGB {
+        let logical = switches::this_side().switches[idx].logical;
+
+        let mut c = base;
+
+        if self.layer != 0 && (self.layer as usize) < LAYER_COLOURS.len() {
+            let amount = if is_bound(self.layer, logical) {
+                LAYER_HIGHLIGHT
+            } else {
+                LAYER_WASH
+            };
+
+            c = blend(c, LAYER_COLOURS[self.layer as usize], amount);
+        }
+
+        if let Some(colour) = self.key_colours[idx] {
+            c = colour;
+        }
+
+        c = self.one_shot(idx, c);
+
+        if let Some(n) = MOD_INDICATORS.iter().position(|&p| p == logical) {
+            // left and right modifiers are shown together
+            let bits = (1 << n) | (1 << (n + 4));
+            if self.indicators.held_mods & bits != 0 {
+                c = MOD_COLOUR;
+            }
+        }
+
+        if logical == CAPS_LOCK_INDICATOR && self.indicators.caps_lock {
+            c = CAPS_LOCK_COLOUR;
+        }
+
+        if logical == CAPS_WORD_INDICATOR && self.indicators.caps_word {
+            c = CAPS_WORD_COLOUR;
+        }
+
+        if logical == HOST_INDICATOR && self.host_changed.elapsed() < HOST_INDICATOR_DURATION {
+            c = self
+                .indicators
+                .ble_host
+                .map_or(USB_HOST_COLOUR, host_colour);
+        }
+
+        c
+    }
+
+    /// Tint the thumb cluster while one-shots are active, more strongly when
+    /// locked
+    fn one_shot(&self, idx: usize, base: ColorRGB) -> ColorRGB {
+        if !layout::THUMB_CLUSTER.contains(&idx) {
+            return base;
+        }
+
+        let state = &self.one_shot;
+        let amount = if state.locked_mods != 0 || state.locked_layer.is_some() {
+            220
+        } else if state.is_active() {
+            140
+        } else {
+            return base;
+        };
+
+        blend(base, ONE_SHOT_COLOUR, amount)
+    }
+}
@@ -0,0 +1,108 @@
+//! Keeps the current drawn by the LEDs within a budget
+//!
+//! The current is estimated from each frame before gamma correction, at the
+//! level it's about to be shown at. Gamma correction only ever makes colours
+//! dimmer, so this overestimates slightly. If a frame would go over the budget
+//! for how we're powered (USB or battery) the level is lowered so it fits.
+
+use cichlid::ColorRGB;
+use embassy_time::{Duration, Instant};
+use fixed::types::U16F16;
+use shared::rgb::{power_scale, PowerBudget};
+
+use crate::{flash, metrics, state::USB_CONNECTED, sync::Watch};
+
+use super::layout::NUM_LEDS;
+
+/// Current drawn by one colour channel of a WS2812 at full duty, in microamps
+const CHANNEL_UA: u32 = 12_000;
+
+/// Current drawn by a WS2812 with all channels off, in microamps
+const IDLE_UA: u32 = 1_000;
+
+/// How often the estimate and scaling are reported to metrics
+const REPORT_PERIOD: Duration = Duration::from_secs(1);
+
+/// The least the LEDs can draw, lower budgets are raised to this
+const MIN_BUDGET_MA: u16 = (IDLE_UA * NUM_LEDS as u32).div_ceil(1000) as u16;
+
+static BUDGET: Watch<PowerBudget> = Watch::new(PowerBudget::DEFAULT);
+
+fn clamped(budget: PowerBudget) -> PowerBudget {
+    PowerBudget {
+        usb_ma: budget.usb_ma.max(MIN_BUDGET_MA),
+        battery_ma: budget.battery_ma.max(MIN_BUDGET_MA),
+    }
+}
+
+/// Restore the saved budget
+pub async fn load() {
+    if let Some(b) = flash::get::<PowerBudget>().await {
+        BUDGET.set(clamped(b));
+    }
+}
+
+pub async fn set_budget(budget: PowerBudget) {
+    let budget = clamped(budget);
+
+    crate::log::info!("Setting LED power budget to {}", budget);
+
+    BUDGET.set(budget);
+    flash::set(&budget).await;
+}
+
+/// Estimated current draw of some LEDs shown at `level`, in microamps
+fn estimate_ua(colours: &[ColorRGB], level: U16F16) -> u32 {
+    colours
+        .iter()
+        .map(|c| {
+            let duty = c.r as u32 + c.g as u32 + c.b as u32;
+            let duty = (U16F16::from_num(duty) * level).to_num::<u32>();
+            IDLE_UA + duty * CHANNEL_UA / 255
+        })
+        .sum()
+}
+
+pub struct PowerLimiter {
+    last_report: Instant,
+}
+
+impl Default for PowerLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PowerLimiter {
+    pub fn new() -> Self {
+        Self {
+            last_report: Instant::now(),
+        }
+    }
+
+    /// The level to show a frame at so it fits in the current budget, no
+    /// higher than `level`
+    pub fn limit(&mut self, colours: &[ColorRGB], level: U16F16) -> U16F16 {
+        let budget = BUDGET.current();
+        let budget_ma = if USB_CONNECTED.current() {
+            budget.usb_ma
+        } else {
+            budget.battery_ma
+        };
+
+        let estimate = estimate_ua(colours, level);
+        let idle = IDLE_UA * colours.len() as u32;
+        let scale = power_scale(budget_ma, estimate, idle);
+
+        if self.last_report.elapsed() > REPORT_PERIOD {
+            self.last_report = Instant::now();
+
+            metrics::report_led_power(metrics::LedPower {
+                estimate_ma: (estimate / 1000).min(u16::MAX as u32) as u16,
+                scale_percent: (scale * 100 / 256) as u8,
+            });
+        }
+
+        level * U16F16::from_num(scale) / U16F16::from_num(256)
+    }
+}
@@ -2,7 +2,7 @@ use core::{array, num::NonZeroU8};
 
 use cichlid::ColorRGB;
 use embassy_futures::select::{select, select3};
-use embassy_nrf::peripherals::PWM0;
+use embassy_nrf::{gpio::Output, peripherals::PWM0};
 use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
 use embassy_time::{Duration, Instant, Timer};
 use fixed::types::{U16F16, U32F32};
@@ -17,17 +17,26 @@ use crate::{
 use super::{
     animation::Animation,
     animations,
+    brightness::{self, Ramp},
+    direct,
     driver::Ws2812,
+    idle::{self, Fade},
+    key_colours,
     layout::{self, Light, NUM_LEDS},
     math_utils::{blend, ease_fade},
-    RGB_CMD_CHANNEL,
+    notification::NotificationOverlay,
+    overlay::Overlay,
+    power::{self, PowerLimiter},
+    KEYPRESS_LOCATIONS, RGB_CMD_CHANNEL,
 };
 
-const MAX_LEVEL: u8 = 255;
 // I use Kailh sunsets on my glove80, this compensates for that
 const COLOUR_CORRECTION: ColorRGB = ColorRGB::new(190, 200, 255);
 const FADE_DURATION: Duration = Duration::from_secs(3);
 
+/// How long the LEDs take to start up after their power is switched back on
+const LEDS_POWER_UP: Duration = Duration::from_millis(1);
+
 fn ease_fade_on_time(duration: Duration) -> u8 {
     if duration > FADE_DURATION {
         255
@@ -87,7 +96,6 @@ impl<'a, T: Animation> PerformingAnimation<'a, T> {
 
         for (dest, light) in self.colours.iter_mut().zip(self.lights) {
             let mut color = self.animation.render(light);
-            color.scale(MAX_LEVEL);
             color.scale_from_other(COLOUR_CORRECTION);
 
             *dest = color;
@@ -96,7 +104,10 @@ impl<'a, T: Animation> PerformingAnimation<'a, T> {
 }
 
 #[embassy_executor::task]
-pub async fn rgb_runner(mut driver: Ws2812<PWM0, { NUM_LEDS as usize }>) {
+pub async fn rgb_runner(
+    mut driver: Ws2812<PWM0, { NUM_LEDS as usize }>,
+    mut power_rail: Output<'static>,
+) {
     let mut current_colours = [ColorRGB::Black; NUM_LEDS as usize];
     let mut next_colours = [ColorRGB::Black; NUM_LEDS as usize];
 
@@ -106,32 +117,36 @@ pub async fn rgb_runner(mut driver: Ws2812<PWM0, { NUM_LEDS as usize }>) {
         &layout::RIGHT
     };
 
+    brightness::load().await;
+    let mut ramp = Ramp::new();
+
+    idle::load().await;
+    let mut fade = Fade::new();
+
+    power::load().await;
+    let mut power = PowerLimiter::new();
+
+    key_colours::load().await;
+    let mut overlay = Overlay::new(lights);
+    let mut notification = NotificationOverlay::new();
+
     let mut current = PerformingAnimation::new(
         animations::DynAnimation::Null(animations::null::Null),
         &mut current_colours,
         &lights.lights,
     );
 
-    let mut next: Option<(Instant, PerformingAnimation<'_, animations::DynAnimation>)> =
-        if crate::side::is_master() {
-            let animation = PerformingAnimation::new(
-                animations::DynAnimation::random(),
-                &mut next_colours,
-                &lights.lights,
-            );
-
-            // reporo the animation to the other side
-            let cmd = DeviceToDevice::SetAnimation(animation.animation.construct_sync());
-            interboard::send_msg(cmd, 3).await;
-
-            Some((Instant::now(), animation))
-        } else {
-            None
-        };
+    // the master's animation selector sends the first animation
+    let mut next: Option<(Instant, PerformingAnimation<'_, animations::DynAnimation>)> = None;
 
     let mut last_sync = Instant::now();
     const SYNC_PERIOD: Duration = Duration::from_secs(10);
 
+    let mut powered = true;
+
+    // a command received while the LEDs were powered off
+    let mut pending_cmd = None;
+
     loop {
         let mut errors = [GammaErrorTracker::default(); NUM_LEDS as usize];
 
@@ -145,9 +160,23 @@ pub async fn rgb_runner(mut driver: Ws2812<PWM0, { NUM_LEDS as usize }>) {
 
             let cmd = DeviceToDevice::SyncAnimation(current.animation.construct_sync());
             let _ = interboard::try_send_msg(cmd, 3);
+
+            // keep the other side's brightness in step too, in case it missed a change
+            let _ =
+                interboard::try_send_msg(DeviceToDevice::SetBrightness(brightness::target()), 3);
         }
 
-        if let Ok(cmd) = RGB_CMD_CHANNEL.try_receive() {
+        while let Ok(location) = KEYPRESS_LOCATIONS.try_receive() {
+            current.animation.keypress(location);
+            if let Some((_, next)) = next.as_mut() {
+                next.animation.keypress(location);
+            }
+        }
+
+        if let Some(cmd) = pending_cmd
+            .take()
+            .or_else(|| RGB_CMD_CHANNEL.try_receive().ok())
+        {
             match cmd {
                 super::Command::SetNextAnimation(a) => {
                     next = Some((
@@ -176,6 +205,33 @@ pub async fn rgb_runner(mut driver: Ws2812<PWM0, { NUM_LEDS as usize }>) {
             }
         }
 
+        if fade.is_off() {
+            if powered {
+                crate::log::info!("LEDs idle, powering them off");
+
+                driver.write(&[ColorRGB::Black; NUM_LEDS as usize]).await;
+                power_rail.set_low();
+                powered = false;
+            }
+
+            // keep taking commands while powered off, so the animation selector
+            // and the other side aren't left waiting on us
+            match select(idle::wait_for_wake(), RGB_CMD_CHANNEL.receive()).await {
+                embassy_futures::select::Either::First(()) => {
+                    power_rail.set_high();
+                    powered = true;
+                    fade.restart();
+
+                    // give the LEDs a moment to power up before sending them anything
+                    Timer::after(LEDS_POWER_UP).await;
+                }
+                embassy_futures::select::Either::Second(cmd) => {
+                    pending_cmd = Some(cmd);
+                    continue;
+                }
+            }
+        }
+
         loop {
             if let Some((fade_start, next)) = next.as_mut() {
                 match select3(
@@ -192,18 +248,33 @@ pub async fn rgb_runner(mut driver: Ws2812<PWM0, { NUM_LEDS as usize }>) {
                         break;
                     }
                     embassy_futures::select::Either3::Third(_) => {
+                        overlay.update().await;
+                        notification.update();
+                        let level = ramp.update() * fade.update();
+                        let direct = direct::frame().await;
                         let sparkles = KEY_SPARKLES.lock().await;
-                        let corrected_colours =
-                            array::from_fn::<_, { NUM_LEDS as usize }, _>(|i| {
+                        let colours = array::from_fn::<_, { NUM_LEDS as usize }, _>(|i| {
+                            let c = if let Some(frame) = &direct {
+                                frame[i]
+                            } else {
                                 let a = current.colours[i];
                                 let b = next.colours[i];
                                 let c = blend(a, b, ease_fade_on_time(fade_start.elapsed()));
-                                let d = maybe_sparkle(sparkles[i], c);
-                                errors[i].process(d)
-                            });
+                                let d = overlay.apply(i, c);
+                                maybe_sparkle(sparkles[i], d)
+                            };
+
+                            notification.apply(i, c)
+                        });
 
                         drop(sparkles);
 
+                        let level = power.limit(&colours, level);
+                        let corrected_colours =
+                            array::from_fn::<_, { NUM_LEDS as usize }, _>(|i| {
+                                errors[i].process(colours[i], level)
+                            });
+
                         driver.write(&corrected_colours).await;
                     }
                 }
@@ -213,15 +284,30 @@ pub async fn rgb_runner(mut driver: Ws2812<PWM0, { NUM_LEDS as usize }>) {
                         break;
                     }
                     embassy_futures::select::Either::Second(_) => {
+                        overlay.update().await;
+                        notification.update();
+                        let level = ramp.update() * fade.update();
+                        let direct = direct::frame().await;
                         let sparkles = KEY_SPARKLES.lock().await;
+                        let colours = array::from_fn::<_, { NUM_LEDS as usize }, _>(|i| {
+                            let c = if let Some(frame) = &direct {
+                                frame[i]
+                            } else {
+                                let c = overlay.apply(i, current.colours[i]);
+                                maybe_sparkle(sparkles[i], c)
+                            };
+
+                            notification.apply(i, c)
+                        });
+
+                        drop(sparkles);
+
+                        let level = power.limit(&colours, level);
                         let corrected_colours =
                             array::from_fn::<_, { NUM_LEDS as usize }, _>(|i| {
-                                let c = maybe_sparkle(sparkles[i], current.colours[i]);
-                                errors[i].process(c)
+                                errors[i].process(colours[i], level)
                             });
 
-                        drop(sparkles);
-
                         driver.write(&corrected_colours).await;
                     }
                 }
@@ -264,6 +350,7 @@ pub async fn apply_keypresses() {
         };
 
         let idx = lights.inverse_index[x as usize][y as usize];
+        super::keypress_at(lights.lights[idx as usize].location);
 
         let mut l = KEY_SPARKLES.lock().await;
         l[idx as usize] = Some(NonZeroU8::MIN);
@@ -292,19 +379,21 @@ struct GammaErrorTracker {
 }
 
 impl GammaErrorTracker {
-    fn process(&mut self, color: ColorRGB) -> ColorRGB {
+    /// Gamma correct a colour and scale it by `level`, carrying the fractional
+    /// part over to the next frame
+    fn process(&mut self, color: ColorRGB, level: U16F16) -> ColorRGB {
         // color.modify_all(|i| GAMMA[i as usize].int().saturating_to_num());
         // return color;
 
-        let r = GAMMA[color.r as usize] + self.r;
+        let r = GAMMA[color.r as usize] * level + self.r;
         self.r = r.frac();
         let r = r.int().saturating_to_num();
 
-        let g = GAMMA[color.g as usize] + self.g;
+        let g = GAMMA[color.g as usize] * level + self.g;
         self.g = g.frac();
         let g = g.int().saturating_to_num();
 
-        let b = GAMMA[color.b as usize] + self.b;
+        let b = GAMMA[color.b as usize] * level + self.b;
         self.b = b.frac();
         let b = b.int().saturating_to_num();
 
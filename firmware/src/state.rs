@@ -4,6 +4,41 @@ use crate::sync::{mutex, Mutex, Watch};
 
 pub static USB_CONNECTED: Watch<bool> = Watch::new(false);
 
+/// Address of the BLE host connected to our HID service, if any
+pub static BLE_HID_HOST: Watch<Option<[u8; 6]>> = Watch::new(None);
+
+/// Keyboard LEDs (num lock, caps lock, ...) last set by each kind of host, as
+/// HID LED bits
+pub static USB_HOST_LEDS: Watch<u8> = Watch::new(0);
+pub static BLE_HOST_LEDS: Watch<u8> = Watch::new(0);
+
+/// Caps lock in the HID LED bits
+pub const CAPS_LOCK_LED: u8 = 1 << 1;
+
+/// A host we can send key presses to, used to remember per host settings
+#[derive(serde::Serialize, serde::Deserialize, Eq, PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub enum HostId {
+    Usb,
+    Ble([u8; 6]),
+}
+
+/// The host key presses are currently going to
+pub fn current_host() -> HostId {
+    match BLE_HID_HOST.current() {
+        Some(addr) => HostId::Ble(addr),
+        None => HostId::Usb,
+    }
+}
+
+/// Keyboard LEDs of the host key presses are currently going to
+pub fn current_host_leds() -> u8 {
+    match current_host() {
+        HostId::Ble(_) => BLE_HOST_LEDS.current(),
+        HostId::Usb => USB_HOST_LEDS.current(),
+    }
+}
+
 pub async fn wait_usb_connected() {
     USB_CONNECTED.wait_for(|c| *c).await;
 }
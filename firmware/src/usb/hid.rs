@@ -1,12 +1,15 @@
 use embassy_executor::Spawner;
 use embassy_sync::channel::Channel;
-use embassy_usb::{class::hid::HidWriter, Builder};
+use embassy_usb::{
+    class::hid::{HidWriter, OutResponse, ReportId, RequestHandler},
+    Builder,
+};
 use packed_struct::PackedStruct;
 use usbd_human_interface_device::device::keyboard::{
     NKROBootKeyboardReport, NKRO_BOOT_KEYBOARD_REPORT_DESCRIPTOR,
 };
 
-use crate::utils;
+use crate::{state::USB_HOST_LEDS, utils};
 
 use super::USBDriver;
 
@@ -26,6 +29,19 @@ async fn keyboard_writer(mut keyboard_writer: HidWriter<'static, USBDriver, 64>)
         let _ = keyboard_writer.write(&report.pack().unwrap()).await;
     }
 }
+/// Picks up the host's keyboard LEDs, which it sets with SET_REPORT
+struct HostLeds;
+
+impl RequestHandler for HostLeds {
+    fn set_report(&mut self, id: ReportId, data: &[u8]) -> OutResponse {
+        if let (ReportId::Out(_), Some(&leds)) = (id, data.first()) {
+            USB_HOST_LEDS.set(leds);
+        }
+
+        OutResponse::Accepted
+    }
+}
+
 pub fn init(spawner: &Spawner, builder: &mut Builder<'static, USBDriver>) {
     let keyboard_state = utils::singleton!(
         embassy_usb::class::hid::State,
@@ -37,7 +53,7 @@ pub fn init(spawner: &Spawner, builder: &mut Builder<'static, USBDriver>) {
         keyboard_state,
         embassy_usb::class::hid::Config {
             report_descriptor: NKRO_BOOT_KEYBOARD_REPORT_DESCRIPTOR,
-            request_handler: None,
+            request_handler: Some(utils::singleton!(HostLeds, HostLeds)),
             poll_ms: 2,
             max_packet_size: 64,
         },
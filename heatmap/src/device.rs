@@ -0,0 +1,53 @@
+use std::{
+    io::{ErrorKind, Read},
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use host_serial::Connection;
+use postcard::accumulator::{CobsAccumulator, FeedResult};
+use shared::{cmd::CmdOrAck, device_to_host::DeviceToHost, host_to_device::HostToDeviceMsg};
+
+use crate::Counts;
+
+/// How long to wait for the keyboard to send everything
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Ask the keyboard connected on `port` for its key counts
+pub fn download(port: &str) -> anyhow::Result<Counts> {
+    let mut keyboard = Connection::new(host_serial::open(port)?);
+    keyboard.send(HostToDeviceMsg::GetKeyCounts)?;
+    let port = keyboard.port();
+
+    let mut counts = Counts::default();
+    let mut accumulator = CobsAccumulator::<256>::new();
+    let started = Instant::now();
+
+    while started.elapsed() < TIMEOUT {
+        let mut buf = [0u8; 64];
+        let n = match port.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::TimedOut => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let mut window = &buf[..n];
+
+        while !window.is_empty() {
+            window = match accumulator.feed::<CmdOrAck<DeviceToHost>>(window) {
+                FeedResult::Consumed => break,
+                FeedResult::OverFull(rest) | FeedResult::DeserError(rest) => rest,
+                FeedResult::Success { data, remaining } => {
+                    if let CmdOrAck::Cmd(c) = data {
+                        if c.validate() && counts.receive(c.cmd.msg) {
+                            return Ok(counts);
+                        }
+                    }
+
+                    remaining
+                }
+            };
+        }
+    }
+
+    bail!("timed out waiting for key counts, is the keyboard connected over USB?")
+}
@@ -0,0 +1,121 @@
+//! Download per key press counts from the keyboard and render them as a
+//! heatmap over the layout in `layouts/rusty-glove-layout.json`
+//!
+//! ```text
+//! heatmap download <serial port> <counts.json>
+//! heatmap render <counts.json> <layout.json> <layer|all> <out.svg>
+//! ```
+
+mod device;
+mod render;
+
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use shared::{device_to_host::DeviceToHostMsg, switches::LAYOUT_COLS};
+
+/// Press counts as sent by the keyboard, indexed by layer, row and column of
+/// the keymap
+#[derive(Serialize, Deserialize, Default)]
+pub struct Counts {
+    pub layers: Vec<Vec<[u32; LAYOUT_COLS]>>,
+    pub left: u32,
+    pub right: u32,
+}
+
+impl Counts {
+    /// Take in a message from the keyboard, returns true once all the counts
+    /// have arrived
+    fn receive(&mut self, msg: DeviceToHostMsg) -> bool {
+        match msg {
+            DeviceToHostMsg::KeyCounts { layer, row, counts } => {
+                let (layer, row) = (layer as usize, row as usize);
+
+                if self.layers.len() <= layer {
+                    self.layers.resize(layer + 1, Vec::new());
+                }
+
+                let rows = &mut self.layers[layer];
+                if rows.len() <= row {
+                    rows.resize(row + 1, [0; LAYOUT_COLS]);
+                }
+
+                rows[row] = counts;
+
+                false
+            }
+            DeviceToHostMsg::SideKeyCounts { left, right } => {
+                self.left = left;
+                self.right = right;
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Count for a (column, row) of the keymap, on one layer or summed over
+    /// all of them
+    fn get(&self, layer: Option<usize>, (col, row): (u8, u8)) -> u32 {
+        let of_layer = |rows: &Vec<[u32; LAYOUT_COLS]>| {
+            rows.get(row as usize)
+                .and_then(|r| r.get(col as usize))
+                .copied()
+                .unwrap_or(0)
+        };
+
+        match layer {
+            Some(l) => self.layers.get(l).map_or(0, of_layer),
+            None => self.layers.iter().map(of_layer).sum(),
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage:");
+    eprintln!("  heatmap download <serial port> <counts.json>");
+    eprintln!("  heatmap render <counts.json> <layout.json> <layer|all> <out.svg>");
+    std::process::exit(1)
+}
+
+fn read_counts(path: &Path) -> anyhow::Result<Counts> {
+    let file = std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["download", port, out] => {
+            let counts = device::download(port)?;
+            std::fs::write(out, serde_json::to_string_pretty(&counts)?)?;
+
+            println!(
+                "Saved counts for {} layers ({} left, {} right presses)",
+                counts.layers.len(),
+                counts.left,
+                counts.right
+            );
+        }
+        ["render", counts, layout, layer, out] => {
+            let counts = read_counts(Path::new(counts))?;
+            let layout = render::Layout::load(Path::new(layout))?;
+
+            let layer = match *layer {
+                "all" => None,
+                l => match l.parse() {
+                    Ok(l) => Some(l),
+                    Err(_) => bail!("layer should be a number or 'all', not {l}"),
+                },
+            };
+
+            std::fs::write(out, render::render(&layout, &counts, layer)?)?;
+        }
+        _ => usage(),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+use crate::Counts;
+
+/// Size of a key unit in the svg
+const U: f32 = 60.0;
+
+/// Gap between keys
+const GAP: f32 = 4.0;
+
+/// A key in a KLE style layout file, rotated by `r` degrees around (`rx`, `ry`)
+#[derive(Deserialize, Clone, Copy)]
+struct Key {
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    r: f32,
+    #[serde(default)]
+    rx: f32,
+    #[serde(default)]
+    ry: f32,
+}
+
+#[derive(Deserialize)]
+struct LayoutDef {
+    layout: Vec<Key>,
+}
+
+#[derive(Deserialize)]
+struct LayoutFile {
+    layouts: HashMap<String, LayoutDef>,
+}
+
+pub struct Layout {
+    keys: Vec<Key>,
+}
+
+impl Layout {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let file: LayoutFile = serde_json::from_reader(file)?;
+
+        let Some(def) = file.layouts.into_values().next() else {
+            bail!("{} doesn't have any layouts", path.display());
+        };
+
+        if def.layout.len() != KEYS.len() {
+            bail!(
+                "expected {} keys in the layout, found {}",
+                KEYS.len(),
+                def.layout.len()
+            );
+        }
+
+        Ok(Self { keys: def.layout })
+    }
+}
+
+/// Keymap (column, row) of each key, in the order the layout file lists them
+const KEYS: [(u8, u8); 80] = {
+    let mut keys = [(0, 0); 80];
+    let mut n = 0;
+
+    // the function row and bottom row don't have the inner column
+    let mut row = 0;
+    while row < 6 {
+        let mut col = 0;
+        while col < 12 {
+            let inner = col == 5 || col == 6;
+            if !(inner && (row == 0 || row == 5)) {
+                keys[n] = (col, row);
+                n += 1;
+            }
+            col += 1;
+        }
+        row += 1;
+    }
+
+    // thumbs, the upper row of each side then the lower, outermost first
+    let thumbs = [(3, 6), (4, 6), (5, 6), (6, 6), (7, 6), (8, 6)];
+    let mut i = 0;
+    while i < 12 {
+        let (col, row) = thumbs[i % 6];
+        keys[n] = (col, row + (i / 6) as u8);
+        n += 1;
+        i += 1;
+    }
+
+    assert!(n == 80);
+
+    keys
+};
+
+fn rotate((x, y): (f32, f32), key: &Key) -> (f32, f32) {
+    let (s, c) = key.r.to_radians().sin_cos();
+    let (dx, dy) = (x - key.rx * U, y - key.ry * U);
+
+    (key.rx * U + dx * c - dy * s, key.ry * U + dx * s + dy * c)
+}
+
+/// Cold to hot: dark blue, yellow, red
+fn colour(t: f32) -> String {
+    const STOPS: [(f32, f32, f32); 3] = [
+        (40.0, 50.0, 110.0),
+        (250.0, 210.0, 60.0),
+        (220.0, 40.0, 30.0),
+    ];
+
+    let t = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (t as usize).min(STOPS.len() - 2);
+    let f = t - i as f32;
+
+    let (a, b) = (STOPS[i], STOPS[i + 1]);
+    let lerp = |a: f32, b: f32| (a + (b - a) * f).round() as u8;
+
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        lerp(a.0, b.0),
+        lerp(a.1, b.1),
+        lerp(a.2, b.2)
+    )
+}
+
+/// Render the counts for a layer, or all layers summed, as an svg
+pub fn render(layout: &Layout, counts: &Counts, layer: Option<usize>) -> anyhow::Result<String> {
+    let values: Vec<u32> = KEYS.iter().map(|&pos| counts.get(layer, pos)).collect();
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+
+    let corners = layout.keys.iter().flat_map(|k| {
+        [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+            .map(|(cx, cy)| rotate(((k.x + cx) * U, (k.y + cy) * U), k))
+    });
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for (x, y) in corners {
+        (min_x, min_y) = (min_x.min(x), min_y.min(y));
+        (max_x, max_y) = (max_x.max(x), max_y.max(y));
+    }
+
+    let caption_height = U * 0.6;
+    let (width, height) = (
+        max_x - min_x + 2.0 * GAP,
+        max_y - min_y + 2.0 * GAP + caption_height,
+    );
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {width} {height}" font-family="sans-serif">"#,
+        min_x - GAP,
+        min_y - GAP,
+    )?;
+
+    for ((key, value), pos) in layout.keys.iter().zip(&values).zip(KEYS) {
+        let (x, y) = (key.x * U + GAP / 2.0, key.y * U + GAP / 2.0);
+        let size = U - GAP;
+
+        writeln!(
+            svg,
+            r#"  <g transform="rotate({} {} {})"><title>({}, {})</title>"#,
+            key.r,
+            key.rx * U,
+            key.ry * U,
+            pos.0,
+            pos.1
+        )?;
+        writeln!(
+            svg,
+            r##"    <rect x="{x}" y="{y}" width="{size}" height="{size}" rx="6" fill="{}" stroke="#222"/>"##,
+            colour(*value as f32 / max as f32)
+        )?;
+        writeln!(
+            svg,
+            r#"    <text x="{}" y="{}" font-size="13" text-anchor="middle" dominant-baseline="middle">{value}</text>"#,
+            x + size / 2.0,
+            y + size / 2.0
+        )?;
+        writeln!(svg, "  </g>")?;
+    }
+
+    let total = (counts.left + counts.right).max(1) as f32;
+    writeln!(
+        svg,
+        r#"  <text x="{}" y="{}" font-size="16" text-anchor="middle">layer: {}, left: {} ({:.1}%), right: {} ({:.1}%)</text>"#,
+        (min_x + max_x) / 2.0,
+        max_y + caption_height * 0.7,
+        layer.map_or("all".to_owned(), |l| l.to_string()),
+        counts.left,
+        counts.left as f32 * 100.0 / total,
+        counts.right,
+        counts.right as f32 * 100.0 / total,
+    )?;
+
+    writeln!(svg, "</svg>")?;
+
+    Ok(svg)
+}
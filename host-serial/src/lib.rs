@@ -0,0 +1,67 @@
+//! Talking to the keyboard over its USB serial port, for the host side tools
+
+use std::{
+    io::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+pub use serialport::SerialPort;
+use shared::{
+    cmd::{CmdOrAck, Command},
+    host_to_device::{HostToDevice, HostToDeviceMsg},
+};
+
+/// Open the keyboard's serial port
+pub fn open(port: &str) -> anyhow::Result<Box<dyn SerialPort>> {
+    serialport::new(port, 115_200)
+        .timeout(Duration::from_millis(100))
+        .open()
+        .with_context(|| format!("opening {port}"))
+}
+
+/// Sends commands to the keyboard, or anything else that takes its host
+/// protocol
+pub struct Connection<W> {
+    port: W,
+    id: u8,
+}
+
+impl<W: Write> Connection<W> {
+    pub fn new(port: W) -> Self {
+        // the keyboard drops a command with the same id as the last one it
+        // saw, so start somewhere different each run and move on for every
+        // command after that
+        let id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |t| (t.subsec_nanos() & 0x7f) as u8);
+
+        Self { port, id }
+    }
+
+    /// Send a command to both sides, without waiting for an ack
+    pub fn send(&mut self, msg: HostToDeviceMsg) -> anyhow::Result<()> {
+        self.id = (self.id + 1) & 0x7f;
+
+        let cmd = CmdOrAck::Cmd(Command::new_unreliable(
+            HostToDevice {
+                target_side: None,
+                msg,
+            },
+            self.id,
+        ));
+        self.port.write_all(&postcard::to_stdvec_cobs(&cmd)?)?;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.port.flush()?;
+        Ok(())
+    }
+
+    /// The port, for reading what the keyboard sends back
+    pub fn port(&mut self) -> &mut W {
+        &mut self.port
+    }
+}
@@ -0,0 +1,38 @@
+use std::io::Write;
+
+use host_serial::{Connection, SerialPort};
+use shared::{
+    host_to_device::{HostToDeviceMsg, MAX_DIRECT_LEDS_CHUNK},
+    rgb::Rgb,
+};
+
+/// The keyboard, or anything else that takes its host protocol
+pub struct Keyboard<W> {
+    connection: Connection<W>,
+}
+
+impl Keyboard<Box<dyn SerialPort>> {
+    pub fn open(port: &str) -> anyhow::Result<Self> {
+        Ok(Self::new(host_serial::open(port)?))
+    }
+}
+
+impl<W: Write> Keyboard<W> {
+    pub fn new(port: W) -> Self {
+        Self {
+            connection: Connection::new(port),
+        }
+    }
+
+    /// Show a frame on both sides, the left side's LEDs first
+    pub fn send_frame(&mut self, colours: &[Rgb]) -> anyhow::Result<()> {
+        for (n, chunk) in colours.chunks(MAX_DIRECT_LEDS_CHUNK).enumerate() {
+            self.connection.send(HostToDeviceMsg::DirectLeds {
+                offset: (n * MAX_DIRECT_LEDS_CHUNK) as u8,
+                colours: heapless::Vec::from_slice(chunk).unwrap(),
+            })?;
+        }
+
+        self.connection.flush()
+    }
+}
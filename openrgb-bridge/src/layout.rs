@@ -0,0 +1,84 @@
+//! The keyboard as OpenRGB sees it, one matrix zone per side built from the
+//! switch locations in [`shared::switches`]
+
+use shared::switches::{Switches, LEFT, RIGHT};
+
+/// Horizontal distance between neighbouring columns of switches (mm)
+const COL_PITCH: i16 = 20;
+
+/// The thumb clusters are offset by half a key vertically, so rows are split
+/// finer than columns to keep every switch in its own cell
+const ROW_PITCH: i16 = 10;
+
+/// Matrix cells without an LED
+pub const NO_LED: u32 = u32::MAX;
+
+pub struct Led {
+    pub name: String,
+}
+
+pub struct Zone {
+    pub name: &'static str,
+    /// Index of the zone's first LED in the whole keyboard
+    pub first_led: usize,
+    pub leds: Vec<Led>,
+    pub height: u32,
+    pub width: u32,
+    /// LED index (within the zone) of each cell, row by row
+    pub matrix: Vec<u32>,
+}
+
+fn zone(name: &'static str, first_led: usize, switches: &Switches) -> Zone {
+    let locations = switches.switches.map(|s| s.location);
+
+    let min_x = locations.iter().map(|l| l.0).min().unwrap_or(0);
+    let max_y = locations.iter().map(|l| l.1).max().unwrap_or(0);
+
+    let cells: Vec<(usize, usize)> = locations
+        .iter()
+        .map(|&(x, y)| {
+            let col = (x - min_x).div_euclid(COL_PITCH) as usize;
+            let row = (max_y - y).div_euclid(ROW_PITCH) as usize;
+            (col, row)
+        })
+        .collect();
+
+    let width = cells.iter().map(|c| c.0 + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|c| c.1 + 1).max().unwrap_or(0);
+
+    let mut matrix = vec![NO_LED; width * height];
+    for (idx, &(col, row)) in cells.iter().enumerate() {
+        let cell = &mut matrix[row * width + col];
+        assert!(*cell == NO_LED, "two LEDs in the same matrix cell");
+        *cell = idx as u32;
+    }
+
+    let leds = switches
+        .switches
+        .iter()
+        .map(|s| Led {
+            name: format!("Key {},{}", s.logical.0, s.logical.1),
+        })
+        .collect();
+
+    Zone {
+        name,
+        first_led,
+        leds,
+        height: height as u32,
+        width: width as u32,
+        matrix,
+    }
+}
+
+/// Both sides, in the order their LEDs appear in a direct frame
+pub fn zones() -> Vec<Zone> {
+    vec![
+        zone("Left", 0, &LEFT),
+        zone("Right", LEFT.switches.len(), &RIGHT),
+    ]
+}
+
+pub fn num_leds() -> usize {
+    zones().iter().map(|z| z.leds.len()).sum()
+}
@@ -0,0 +1,44 @@
+//! Presents the keyboard to OpenRGB (and anything else speaking its network
+//! SDK protocol) as a keyboard with 80 LEDs, colours set by clients are
+//! streamed to the keyboard as direct LED frames
+//!
+//! ```text
+//! openrgb-bridge <serial port> [listen address]
+//! ```
+//!
+//! The listen address defaults to OpenRGB's usual `127.0.0.1:6742`, so the
+//! bridge can be added in OpenRGB as a client connection.
+
+mod device;
+mod layout;
+mod protocol;
+mod server;
+
+use std::net::TcpListener;
+
+use anyhow::Context;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:6742";
+
+fn usage() -> ! {
+    eprintln!("usage: openrgb-bridge <serial port> [listen address]");
+    std::process::exit(1)
+}
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let (port, address) = match args.as_slice() {
+        [port] => (*port, DEFAULT_ADDRESS),
+        [port, address] => (*port, *address),
+        _ => usage(),
+    };
+
+    let keyboard = device::Keyboard::open(port)?;
+    let listener = TcpListener::bind(address).with_context(|| format!("listening on {address}"))?;
+
+    println!("Listening for OpenRGB clients on {address}");
+
+    server::Bridge::new(keyboard).serve(listener)
+}
@@ -0,0 +1,221 @@
+//! The parts of the OpenRGB network SDK protocol we need
+//!
+//! Every packet starts with a header of `ORGB`, the device index, the packet
+//! id and the length of the data that follows, all integers are little
+//! endian.
+
+use std::io::{Read, Write};
+
+use anyhow::bail;
+use shared::rgb::Rgb;
+
+use crate::layout::Zone;
+
+const MAGIC: &[u8; 4] = b"ORGB";
+
+/// The newest protocol version we speak, clients asking for newer get this
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub const REQUEST_CONTROLLER_COUNT: u32 = 0;
+pub const REQUEST_CONTROLLER_DATA: u32 = 1;
+pub const REQUEST_PROTOCOL_VERSION: u32 = 40;
+pub const SET_CLIENT_NAME: u32 = 50;
+pub const RGBCONTROLLER_RESIZEZONE: u32 = 1000;
+pub const RGBCONTROLLER_UPDATELEDS: u32 = 1050;
+pub const RGBCONTROLLER_UPDATEZONELEDS: u32 = 1051;
+pub const RGBCONTROLLER_UPDATESINGLELED: u32 = 1052;
+pub const RGBCONTROLLER_SETCUSTOMMODE: u32 = 1100;
+pub const RGBCONTROLLER_UPDATEMODE: u32 = 1101;
+
+const DEVICE_TYPE_KEYBOARD: i32 = 5;
+const ZONE_TYPE_MATRIX: i32 = 2;
+const MODE_FLAG_HAS_PER_LED_COLOR: u32 = 1 << 5;
+const MODE_COLORS_PER_LED: u32 = 1;
+
+pub struct Packet {
+    pub device: u32,
+    pub id: u32,
+    pub data: Vec<u8>,
+}
+
+pub fn read_packet(r: &mut impl Read) -> anyhow::Result<Packet> {
+    let mut header = [0u8; 16];
+    r.read_exact(&mut header)?;
+
+    if &header[..4] != MAGIC {
+        bail!("packet doesn't start with {MAGIC:?}");
+    }
+
+    let field = |n: usize| u32::from_le_bytes(header[n..n + 4].try_into().unwrap());
+    let (device, id, len) = (field(4), field(8), field(12));
+
+    let mut data = vec![0u8; len as usize];
+    r.read_exact(&mut data)?;
+
+    Ok(Packet { device, id, data })
+}
+
+pub fn write_packet(w: &mut impl Write, device: u32, id: u32, data: &[u8]) -> anyhow::Result<()> {
+    let mut buf = Vec::with_capacity(16 + data.len());
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&device.to_le_bytes());
+    buf.extend_from_slice(&id.to_le_bytes());
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(data);
+
+    w.write_all(&buf)?;
+    Ok(())
+}
+
+/// Reads the fields of a packet's data in order
+pub struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+
+    fn take<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        if self.0.len() < N {
+            bail!("packet is too short");
+        }
+
+        let (head, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(head.try_into().unwrap())
+    }
+
+    pub fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    pub fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub fn colour(&mut self) -> anyhow::Result<Rgb> {
+        let [r, g, b, _] = self.take()?;
+        Ok(Rgb { r, g, b })
+    }
+
+    pub fn colours(&mut self) -> anyhow::Result<Vec<Rgb>> {
+        let n = self.u16()?;
+        (0..n).map(|_| self.colour()).collect()
+    }
+
+    pub fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.u16()? as usize;
+        if self.0.len() < len {
+            bail!("packet is too short");
+        }
+
+        let (s, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(String::from_utf8_lossy(s.strip_suffix(&[0]).unwrap_or(s)).into_owned())
+    }
+}
+
+/// Builds up a packet's data
+#[derive(Default)]
+pub struct Writer(Vec<u8>);
+
+impl Writer {
+    pub fn u16(&mut self, v: u16) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn u32(&mut self, v: u32) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn i32(&mut self, v: i32) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn colour(&mut self, c: Rgb) -> &mut Self {
+        self.0.extend_from_slice(&[c.r, c.g, c.b, 0]);
+        self
+    }
+
+    pub fn colours(&mut self, colours: &[Rgb]) -> &mut Self {
+        self.u16(colours.len() as u16);
+        for &c in colours {
+            self.colour(c);
+        }
+        self
+    }
+
+    /// Strings are sent with their length and a nul terminator
+    pub fn string(&mut self, s: &str) -> &mut Self {
+        self.u16(s.len() as u16 + 1);
+        self.0.extend_from_slice(s.as_bytes());
+        self.0.push(0);
+        self
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// Description of the keyboard in reply to [`REQUEST_CONTROLLER_DATA`], for a
+/// client speaking `version`
+pub fn controller_data(version: u32, zones: &[Zone], colours: &[Rgb]) -> Vec<u8> {
+    let mut w = Writer::default();
+
+    w.i32(DEVICE_TYPE_KEYBOARD).string("Glove80");
+    if version >= 1 {
+        w.string("MoErgo");
+    }
+    w.string("Rusty glove keyboard")
+        .string(env!("CARGO_PKG_VERSION"))
+        .string("")
+        .string("openrgb-bridge");
+
+    // just the one mode, the host sets every LED
+    w.u16(1).i32(0);
+    w.string("Direct")
+        .i32(0)
+        .u32(MODE_FLAG_HAS_PER_LED_COLOR)
+        .u32(0)
+        .u32(0)
+        .u32(0)
+        .u32(0)
+        .u32(0)
+        .u32(0)
+        .u32(MODE_COLORS_PER_LED)
+        .u16(0);
+
+    w.u16(zones.len() as u16);
+    for zone in zones {
+        let n = zone.leds.len() as u32;
+        w.string(zone.name)
+            .i32(ZONE_TYPE_MATRIX)
+            .u32(n)
+            .u32(n)
+            .u32(n)
+            .u16((8 + 4 * zone.matrix.len()) as u16)
+            .u32(zone.height)
+            .u32(zone.width);
+        for &cell in &zone.matrix {
+            w.u32(cell);
+        }
+    }
+
+    let leds: Vec<_> = zones.iter().flat_map(|z| &z.leds).collect();
+    w.u16(leds.len() as u16);
+    for (idx, led) in leds.iter().enumerate() {
+        w.string(&led.name).u32(idx as u32);
+    }
+
+    w.colours(colours);
+
+    // the data starts with its own length
+    let body = w.finish();
+    let mut out = ((body.len() + 4) as u32).to_le_bytes().to_vec();
+    out.extend(body);
+    out
+}
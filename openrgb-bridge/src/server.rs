@@ -0,0 +1,352 @@
+use std::{
+    io::Write,
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use shared::rgb::Rgb;
+
+use crate::{
+    device::Keyboard,
+    layout::{self, Zone},
+    protocol::{self, Packet, Reader},
+};
+
+/// The keyboard goes back to its animation if it doesn't get a frame for a
+/// couple of seconds, so the current colours are sent again this often
+const RESEND_PERIOD: Duration = Duration::from_millis(500);
+
+struct State<W> {
+    keyboard: Keyboard<W>,
+    colours: Vec<Rgb>,
+    /// Clients that have set colours and are still connected
+    driving: usize,
+}
+
+pub struct Bridge<W> {
+    state: Mutex<State<W>>,
+    zones: Vec<Zone>,
+}
+
+impl<W: Write + Send + 'static> Bridge<W> {
+    pub fn new(keyboard: Keyboard<W>) -> Arc<Self> {
+        let zones = layout::zones();
+
+        Arc::new(Self {
+            state: Mutex::new(State {
+                keyboard,
+                colours: vec![Rgb { r: 0, g: 0, b: 0 }; layout::num_leds()],
+                driving: 0,
+            }),
+            zones,
+        })
+    }
+
+    /// Accept clients forever
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> anyhow::Result<()> {
+        let resender = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(RESEND_PERIOD);
+
+            let mut state = resender.state.lock().unwrap();
+            if state.driving > 0 {
+                let State {
+                    keyboard, colours, ..
+                } = &mut *state;
+
+                if let Err(e) = keyboard.send_frame(colours) {
+                    eprintln!("Couldn't send a frame to the keyboard: {e:#}");
+                }
+            }
+        });
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let bridge = self.clone();
+
+            thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                if let Err(e) = bridge.client(stream) {
+                    eprintln!("Client {peer:?} disconnected: {e:#}");
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    fn client(&self, mut stream: TcpStream) -> anyhow::Result<()> {
+        let mut version = 0;
+        let mut driving = false;
+
+        let result = loop {
+            let packet = match protocol::read_packet(&mut stream) {
+                Ok(p) => p,
+                Err(e) => break Err(e),
+            };
+
+            match self.handle(&mut stream, &mut version, packet) {
+                Ok(updated) => {
+                    if updated && !driving {
+                        driving = true;
+                        self.state.lock().unwrap().driving += 1;
+                    }
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        if driving {
+            self.state.lock().unwrap().driving -= 1;
+        }
+
+        result
+    }
+
+    /// Handle a packet from a client, returns true if it changed the colours
+    fn handle(
+        &self,
+        stream: &mut TcpStream,
+        version: &mut u32,
+        Packet { device, id, data }: Packet,
+    ) -> anyhow::Result<bool> {
+        let mut r = Reader::new(&data);
+
+        match id {
+            protocol::REQUEST_CONTROLLER_COUNT => {
+                protocol::write_packet(stream, 0, id, &1u32.to_le_bytes())?;
+            }
+            protocol::REQUEST_PROTOCOL_VERSION => {
+                let theirs = r.u32().unwrap_or(0);
+                *version = theirs.min(protocol::PROTOCOL_VERSION);
+                protocol::write_packet(stream, 0, id, &protocol::PROTOCOL_VERSION.to_le_bytes())?;
+            }
+            protocol::REQUEST_CONTROLLER_DATA => {
+                let requested = r.u32().unwrap_or(0).min(*version);
+                let colours = self.state.lock().unwrap().colours.clone();
+                let data = protocol::controller_data(requested, &self.zones, &colours);
+                protocol::write_packet(stream, device, id, &data)?;
+            }
+            protocol::RGBCONTROLLER_UPDATELEDS => {
+                let _size = r.u32()?;
+                let colours = r.colours()?;
+                self.update(0, &colours)?;
+                return Ok(true);
+            }
+            protocol::RGBCONTROLLER_UPDATEZONELEDS => {
+                let _size = r.u32()?;
+                let zone = r.u32()? as usize;
+                let colours = r.colours()?;
+
+                if let Some(zone) = self.zones.get(zone) {
+                    let n = colours.len().min(zone.leds.len());
+                    self.update(zone.first_led, &colours[..n])?;
+                    return Ok(true);
+                }
+            }
+            protocol::RGBCONTROLLER_UPDATESINGLELED => {
+                let led = r.u32()? as usize;
+                let colour = r.colour()?;
+                self.update(led, &[colour])?;
+                return Ok(true);
+            }
+            protocol::SET_CLIENT_NAME => {
+                eprintln!("Client connected: {}", r.string().unwrap_or_default());
+            }
+            // there's only the one mode and zones can't be resized
+            protocol::RGBCONTROLLER_SETCUSTOMMODE
+            | protocol::RGBCONTROLLER_UPDATEMODE
+            | protocol::RGBCONTROLLER_RESIZEZONE => {}
+            _ => {
+                eprintln!("Ignoring unknown packet {id}");
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn update(&self, first: usize, colours: &[Rgb]) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let State {
+            keyboard,
+            colours: current,
+            ..
+        } = &mut *state;
+
+        for (dest, &c) in current.iter_mut().skip(first).zip(colours) {
+            *dest = c;
+        }
+
+        keyboard.send_frame(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use postcard::accumulator::{CobsAccumulator, FeedResult};
+    use shared::{
+        cmd::CmdOrAck,
+        host_to_device::{HostToDevice, HostToDeviceMsg},
+        rgb::Rgb,
+    };
+
+    use super::Bridge;
+    use crate::{
+        device::Keyboard,
+        layout,
+        protocol::{self, Reader, Writer},
+    };
+
+    /// Pretends to be the keyboard, collecting the frames the bridge sends
+    fn fake_device(listener: TcpListener) -> impl Iterator<Item = (usize, Vec<Rgb>)> {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut accumulator = CobsAccumulator::<256>::new();
+        let mut pending = Vec::new();
+
+        std::iter::from_fn(move || loop {
+            if let Some(chunk) = pending.pop() {
+                return Some(chunk);
+            }
+
+            let mut buf = [0u8; 64];
+            let n = stream.read(&mut buf).unwrap();
+            let mut window = &buf[..n];
+
+            while !window.is_empty() {
+                window = match accumulator.feed::<CmdOrAck<HostToDevice>>(window) {
+                    FeedResult::Consumed => break,
+                    FeedResult::OverFull(rest) | FeedResult::DeserError(rest) => rest,
+                    FeedResult::Success { data, remaining } => {
+                        if let CmdOrAck::Cmd(c) = data {
+                            assert!(c.validate());
+                            if let HostToDeviceMsg::DirectLeds { offset, colours } = c.cmd.msg {
+                                pending.insert(0, (offset as usize, colours.to_vec()));
+                            }
+                        }
+                        remaining
+                    }
+                };
+            }
+        })
+    }
+
+    fn start() -> (TcpStream, impl Iterator<Item = (usize, Vec<Rgb>)>) {
+        let device_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let device_addr = device_listener.local_addr().unwrap();
+
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let keyboard = Keyboard::new(TcpStream::connect(device_addr).unwrap());
+            Bridge::new(keyboard).serve(server).unwrap();
+        });
+
+        let device = fake_device(device_listener);
+        (TcpStream::connect(server_addr).unwrap(), device)
+    }
+
+    fn request(client: &mut TcpStream, id: u32, data: &[u8]) -> Vec<u8> {
+        protocol::write_packet(client, 0, id, data).unwrap();
+        let reply = protocol::read_packet(client).unwrap();
+        assert_eq!(reply.id, id);
+        reply.data
+    }
+
+    #[test]
+    fn describes_the_keyboard() {
+        let (mut client, _device) = start();
+
+        let version = request(
+            &mut client,
+            protocol::REQUEST_PROTOCOL_VERSION,
+            &3u32.to_le_bytes(),
+        );
+        assert_eq!(version, protocol::PROTOCOL_VERSION.to_le_bytes());
+
+        let count = request(&mut client, protocol::REQUEST_CONTROLLER_COUNT, &[]);
+        assert_eq!(count, 1u32.to_le_bytes());
+
+        let data = request(
+            &mut client,
+            protocol::REQUEST_CONTROLLER_DATA,
+            &1u32.to_le_bytes(),
+        );
+        let mut r = Reader::new(&data);
+        assert_eq!(r.u32().unwrap() as usize, data.len());
+        assert_eq!(r.u32().unwrap(), 5);
+        assert_eq!(r.string().unwrap(), "Glove80");
+        assert_eq!(r.string().unwrap(), "MoErgo");
+
+        let zones = layout::zones();
+        assert_eq!(zones.len(), 2);
+        assert_eq!(layout::num_leds(), 80);
+        for zone in &zones {
+            assert_eq!(zone.leds.len(), 40);
+            assert_eq!(zone.matrix.len() as u32, zone.width * zone.height);
+            let lit = zone.matrix.iter().filter(|&&c| c != layout::NO_LED).count();
+            assert_eq!(lit, 40);
+        }
+    }
+
+    #[test]
+    fn forwards_colours_to_the_keyboard() {
+        let (mut client, mut device) = start();
+
+        let colours: Vec<Rgb> = (0..80)
+            .map(|n| Rgb {
+                r: n,
+                g: 255 - n,
+                b: n / 2,
+            })
+            .collect();
+
+        let mut w = Writer::default();
+        w.u32(4 + 2 + 4 * 80).colours(&colours);
+        protocol::write_packet(
+            &mut client,
+            0,
+            protocol::RGBCONTROLLER_UPDATELEDS,
+            &w.finish(),
+        )
+        .unwrap();
+
+        let mut received = vec![None; 80];
+        while received.iter().any(Option::is_none) {
+            let (offset, chunk) = device.next().unwrap();
+            for (n, c) in chunk.into_iter().enumerate() {
+                received[offset + n] = Some(c);
+            }
+        }
+
+        let received: Vec<Rgb> = received.into_iter().map(Option::unwrap).collect();
+        assert_eq!(received, colours);
+
+        // a single LED update sends the whole frame again
+        let mut w = Writer::default();
+        w.u32(79).colour(Rgb { r: 1, g: 2, b: 3 });
+        protocol::write_packet(
+            &mut client,
+            0,
+            protocol::RGBCONTROLLER_UPDATESINGLELED,
+            &w.finish(),
+        )
+        .unwrap();
+
+        let last = loop {
+            let (offset, chunk) = device.next().unwrap();
+            if offset + chunk.len() == 80 && chunk.last() != colours.last() {
+                break chunk;
+            }
+        };
+        assert_eq!(last.last(), Some(&Rgb { r: 1, g: 2, b: 3 }));
+    }
+}
@@ -0,0 +1,399 @@
+//! Debounce algorithms for switch readings
+//!
+//! Everything here works on millisecond timestamps passed in by the caller so
+//! that the debounce window doesn't depend on how often the matrix is scanned,
+//! and so the algorithms can be run on the host against recorded traces.
+
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+/// Which debounce algorithm to use and its timings
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DebounceConfig {
+    /// Report changes immediately, then ignore the key for `ms`
+    Eager { ms: u8 },
+    /// Report a change once the key has read the same for `ms`
+    Deferred { ms: u8 },
+    /// Report presses immediately and ignore the key for `press_ms`, report
+    /// releases once the key has read released for `release_ms`
+    Asymmetric { press_ms: u8, release_ms: u8 },
+}
+
+impl DebounceConfig {
+    pub const DEFAULT: Self = Self::Eager { ms: 20 };
+}
+
+impl Default for DebounceConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Per key debounce state
+pub trait Debounce {
+    /// Feed in a raw reading taken at `now` (ms), returns the new debounced
+    /// state if it changed
+    fn update(&mut self, raw: bool, now: u32) -> Option<bool>;
+
+    fn is_pressed(&self) -> bool;
+}
+
+fn elapsed(since: u32, now: u32) -> u32 {
+    now.wrapping_sub(since)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Eager {
+    ms: u32,
+    pressed: bool,
+    changed_at: Option<u32>,
+}
+
+impl Eager {
+    pub const fn new(ms: u8) -> Self {
+        Self {
+            ms: ms as u32,
+            pressed: false,
+            changed_at: None,
+        }
+    }
+}
+
+impl Debounce for Eager {
+    fn update(&mut self, raw: bool, now: u32) -> Option<bool> {
+        if let Some(t) = self.changed_at {
+            if elapsed(t, now) < self.ms {
+                return None;
+            }
+
+            self.changed_at = None;
+        }
+
+        if raw == self.pressed {
+            return None;
+        }
+
+        self.pressed = raw;
+        self.changed_at = Some(now);
+
+        Some(raw)
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+/// Known as `sym_defer_pk` in QMK
+#[derive(Clone, Copy, Debug)]
+pub struct Deferred {
+    ms: u32,
+    pressed: bool,
+    differs_since: Option<u32>,
+}
+
+impl Deferred {
+    pub const fn new(ms: u8) -> Self {
+        Self {
+            ms: ms as u32,
+            pressed: false,
+            differs_since: None,
+        }
+    }
+}
+
+impl Debounce for Deferred {
+    fn update(&mut self, raw: bool, now: u32) -> Option<bool> {
+        if raw == self.pressed {
+            self.differs_since = None;
+            return None;
+        }
+
+        let since = *self.differs_since.get_or_insert(now);
+
+        if elapsed(since, now) < self.ms {
+            return None;
+        }
+
+        self.pressed = raw;
+        self.differs_since = None;
+
+        Some(raw)
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Asymmetric {
+    press_ms: u32,
+    release_ms: u32,
+    pressed: bool,
+    pressed_at: Option<u32>,
+    released_since: Option<u32>,
+}
+
+impl Asymmetric {
+    pub const fn new(press_ms: u8, release_ms: u8) -> Self {
+        Self {
+            press_ms: press_ms as u32,
+            release_ms: release_ms as u32,
+            pressed: false,
+            pressed_at: None,
+            released_since: None,
+        }
+    }
+}
+
+impl Debounce for Asymmetric {
+    fn update(&mut self, raw: bool, now: u32) -> Option<bool> {
+        if let Some(t) = self.pressed_at {
+            if elapsed(t, now) < self.press_ms {
+                return None;
+            }
+
+            self.pressed_at = None;
+        }
+
+        if !self.pressed {
+            if raw {
+                self.pressed = true;
+                self.pressed_at = Some(now);
+
+                return Some(true);
+            }
+
+            return None;
+        }
+
+        if raw {
+            self.released_since = None;
+            return None;
+        }
+
+        let since = *self.released_since.get_or_insert(now);
+
+        if elapsed(since, now) < self.release_ms {
+            return None;
+        }
+
+        self.pressed = false;
+        self.released_since = None;
+
+        Some(false)
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+/// Any of the debounce algorithms, picked at runtime from a [`DebounceConfig`]
+#[derive(Clone, Copy, Debug)]
+pub enum Debouncer {
+    Eager(Eager),
+    Deferred(Deferred),
+    Asymmetric(Asymmetric),
+}
+
+impl Debouncer {
+    pub const fn new(config: DebounceConfig) -> Self {
+        match config {
+            DebounceConfig::Eager { ms } => Self::Eager(Eager::new(ms)),
+            DebounceConfig::Deferred { ms } => Self::Deferred(Deferred::new(ms)),
+            DebounceConfig::Asymmetric {
+                press_ms,
+                release_ms,
+            } => Self::Asymmetric(Asymmetric::new(press_ms, release_ms)),
+        }
+    }
+
+    /// Switch to another algorithm, keeping the current debounced state
+    pub fn reconfigure(&mut self, config: DebounceConfig) {
+        let pressed = self.is_pressed();
+
+        *self = Self::new(config);
+
+        match self {
+            Debouncer::Eager(d) => d.pressed = pressed,
+            Debouncer::Deferred(d) => d.pressed = pressed,
+            Debouncer::Asymmetric(d) => d.pressed = pressed,
+        }
+    }
+}
+
+impl Default for Debouncer {
+    fn default() -> Self {
+        Self::new(DebounceConfig::DEFAULT)
+    }
+}
+
+impl Debounce for Debouncer {
+    fn update(&mut self, raw: bool, now: u32) -> Option<bool> {
+        match self {
+            Debouncer::Eager(d) => d.update(raw, now),
+            Debouncer::Deferred(d) => d.update(raw, now),
+            Debouncer::Asymmetric(d) => d.update(raw, now),
+        }
+    }
+
+    fn is_pressed(&self) -> bool {
+        match self {
+            Debouncer::Eager(d) => d.is_pressed(),
+            Debouncer::Deferred(d) => d.is_pressed(),
+            Debouncer::Asymmetric(d) => d.is_pressed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A press and release with a few ms of contact chatter on both edges,
+    /// the shape you get from a worn switch
+    const CHATTERY_TAP: &[(u32, bool)] = &[
+        (0, false),
+        (1, false),
+        (2, true),
+        (3, false),
+        (4, true),
+        (5, true),
+        (6, false),
+        (7, true),
+        (8, true),
+        (9, true),
+        (10, true),
+        (20, true),
+        (30, true),
+        (40, true),
+        (60, true),
+        (80, true),
+        (81, false),
+        (82, true),
+        (83, false),
+        (84, false),
+        (85, true),
+        (86, false),
+        (87, false),
+        (88, false),
+        (90, false),
+        (100, false),
+        (110, false),
+        (120, false),
+        (140, false),
+    ];
+
+    /// A single sample glitch while the key is untouched
+    const GLITCH: &[(u32, bool)] = &[
+        (0, false),
+        (1, false),
+        (2, true),
+        (3, false),
+        (4, false),
+        (10, false),
+        (30, false),
+    ];
+
+    fn run(mut d: impl Debounce, trace: &[(u32, bool)]) -> Vec<(u32, bool)> {
+        trace
+            .iter()
+            .filter_map(|&(t, raw)| d.update(raw, t).map(|s| (t, s)))
+            .collect()
+    }
+
+    #[test]
+    fn eager_reports_first_edge() {
+        assert_eq!(
+            run(Eager::new(10), CHATTERY_TAP),
+            vec![(2, true), (81, false)]
+        );
+    }
+
+    #[test]
+    fn eager_reports_glitches() {
+        assert_eq!(run(Eager::new(10), GLITCH), vec![(2, true), (30, false)]);
+    }
+
+    #[test]
+    fn deferred_waits_for_stable_reading() {
+        assert_eq!(
+            run(Deferred::new(5), CHATTERY_TAP),
+            vec![(20, true), (100, false)]
+        );
+    }
+
+    #[test]
+    fn deferred_ignores_glitches() {
+        assert_eq!(run(Deferred::new(5), GLITCH), Vec::new());
+    }
+
+    #[test]
+    fn asymmetric_is_eager_on_press_and_deferred_on_release() {
+        assert_eq!(
+            run(Asymmetric::new(10, 5), CHATTERY_TAP),
+            vec![(2, true), (100, false)]
+        );
+    }
+
+    #[test]
+    fn zero_length_windows_pass_through() {
+        let trace = &[(0, false), (1, true), (2, false), (3, true)];
+        let expected = vec![(1, true), (2, false), (3, true)];
+
+        assert_eq!(run(Eager::new(0), trace), expected);
+        assert_eq!(run(Deferred::new(0), trace), expected);
+        assert_eq!(run(Asymmetric::new(0, 0), trace), expected);
+    }
+
+    #[test]
+    fn timestamps_wrap() {
+        let start = u32::MAX - 3;
+        let trace: Vec<_> = CHATTERY_TAP
+            .iter()
+            .map(|&(t, raw)| (start.wrapping_add(t), raw))
+            .collect();
+
+        assert_eq!(
+            run(Deferred::new(5), &trace),
+            vec![
+                (start.wrapping_add(20), true),
+                (start.wrapping_add(100), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn reconfigure_keeps_state() {
+        let mut d = Debouncer::new(DebounceConfig::Eager { ms: 10 });
+        assert_eq!(d.update(true, 0), Some(true));
+
+        d.reconfigure(DebounceConfig::Deferred { ms: 5 });
+
+        assert!(d.is_pressed());
+        assert_eq!(d.update(true, 1), None);
+    }
+
+    #[test]
+    fn config_builds_matching_debouncer() {
+        let d = Debouncer::new(DebounceConfig::Asymmetric {
+            press_ms: 10,
+            release_ms: 5,
+        });
+
+        assert_eq!(run(d, CHATTERY_TAP), vec![(2, true), (100, false)]);
+    }
+}
@@ -1,7 +1,7 @@
 use core::hash::Hash;
 use serde::{Deserialize, Serialize};
 
-use crate::side::KeyboardSide;
+use crate::{host_to_device::MAX_SNIPPET_TRIGGER, side::KeyboardSide, switches::LAYOUT_COLS};
 
 pub const MAX_LOG_LEN: usize = 16;
 
@@ -33,5 +33,24 @@ pub struct DeviceToHost {
 )]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DeviceToHostMsg {
-    Log { msg: heapless::Vec<u8, MAX_LOG_LEN> },
+    Log {
+        msg: heapless::Vec<u8, MAX_LOG_LEN>,
+    },
+    /// Whether snippet expansion is on, sent before the list of snippets
+    SnippetsEnabled(bool),
+    Snippet {
+        trigger: heapless::Vec<u8, MAX_SNIPPET_TRIGGER>,
+    },
+    /// Presses of each key in a row of the keymap on one layer
+    KeyCounts {
+        layer: u8,
+        row: u8,
+        counts: [u32; LAYOUT_COLS],
+    },
+    /// Presses on each side, sent after all the
+    /// [`DeviceToHostMsg::KeyCounts`]
+    SideKeyCounts {
+        left: u32,
+        right: u32,
+    },
 }
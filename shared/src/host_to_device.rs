@@ -1,7 +1,24 @@
 use core::hash::Hash;
 use serde::{Deserialize, Serialize};
 
-use crate::side::KeyboardSide;
+use crate::{
+    debounce::DebounceConfig,
+    rgb::{AnimationChoice, LedIdleTimeout, Notification, PowerBudget, RandomizerPolicy, Rgb},
+    side::KeyboardSide,
+    switches::LAYOUT_COLS,
+};
+
+/// Largest piece of text sent in a single [`HostToDeviceMsg::TypeText`]
+pub const MAX_TEXT_CHUNK: usize = 48;
+
+/// Longest trigger a snippet can have, in bytes
+pub const MAX_SNIPPET_TRIGGER: usize = 16;
+
+/// LEDs on each side, the left side's come first in a direct frame
+pub const DIRECT_LEDS_PER_SIDE: usize = 40;
+
+/// Most LEDs sent in a single [`HostToDeviceMsg::DirectLeds`]
+pub const MAX_DIRECT_LEDS_CHUNK: usize = 20;
 
 #[derive(
     Serialize,
@@ -37,4 +54,80 @@ impl HostToDevice {
     postcard::experimental::max_size::MaxSize,
 )]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub enum HostToDeviceMsg {}
+pub enum HostToDeviceMsg {
+    /// Part of some UTF-8 text for the keyboard to type out
+    ///
+    /// Text longer than [`MAX_TEXT_CHUNK`] is split over several messages with
+    /// the same `id` and `seq` counting up from zero, `last` is set on the final
+    /// one. Chunks don't need to end on a char boundary.
+    TypeText {
+        id: u8,
+        seq: u8,
+        last: bool,
+        chunk: heapless::Vec<u8, MAX_TEXT_CHUNK>,
+    },
+    /// Stop typing any text sent by the host
+    CancelTyping,
+    /// Part of the expansion of a snippet, chunked in the same way as
+    /// [`HostToDeviceMsg::TypeText`]
+    ///
+    /// The snippet is saved once the last chunk arrives, replacing any
+    /// existing snippet with the same trigger.
+    SetSnippet {
+        trigger: heapless::Vec<u8, MAX_SNIPPET_TRIGGER>,
+        seq: u8,
+        last: bool,
+        chunk: heapless::Vec<u8, MAX_TEXT_CHUNK>,
+    },
+    DeleteSnippet {
+        trigger: heapless::Vec<u8, MAX_SNIPPET_TRIGGER>,
+    },
+    SetSnippetsEnabled(bool),
+    /// Ask for the trigger of each snippet, sent back as
+    /// [`crate::device_to_host::DeviceToHostMsg::Snippet`]
+    ListSnippets,
+    /// Change the debounce algorithm used by the targeted sides
+    SetDebounce(DebounceConfig),
+    /// Ask for the press count of every key, sent back as a
+    /// [`crate::device_to_host::DeviceToHostMsg::KeyCounts`] for each layer
+    /// and row followed by a
+    /// [`crate::device_to_host::DeviceToHostMsg::SideKeyCounts`]
+    GetKeyCounts,
+    /// Show an animation on both sides, this is remembered across restarts
+    SetAnimation(AnimationChoice),
+    /// Change how the animation changes by itself
+    SetRandomizerPolicy(RandomizerPolicy),
+    /// Set the LED brightness of both sides, this is remembered across restarts
+    SetBrightness(u8),
+    /// Change how much current the LEDs of the targeted sides may draw, this
+    /// is remembered across restarts
+    SetPowerBudget(PowerBudget),
+    /// Set the colours of one row of keys on a layer, shown while that layer
+    /// is active, `None` leaves a key showing the animation. Positions are
+    /// the same as the keymap's and each row is remembered across restarts
+    SetKeyColours {
+        layer: u8,
+        row: u8,
+        colours: [Option<Rgb>; LAYOUT_COLS],
+    },
+    /// Go back to the default key colours on every layer
+    ResetKeyColours,
+    /// Part of a frame for the LEDs to show instead of the animation
+    ///
+    /// LEDs are numbered in chain order, the left side's first then the
+    /// right's, `offset` is the number of the first LED in the chunk. The
+    /// animation comes back if no frames arrive for a couple of seconds.
+    ///
+    /// The side the host is connected to takes these whichever side they
+    /// target, and passes the other side's LEDs on itself.
+    DirectLeds {
+        offset: u8,
+        colours: heapless::Vec<Rgb, MAX_DIRECT_LEDS_CHUNK>,
+    },
+    /// Show an effect over the animation on both sides, replacing any
+    /// notification already being shown
+    Notify(Notification),
+    /// Change how long the LEDs of the targeted sides stay on without any key
+    /// presses, this is remembered across restarts
+    SetLedIdleTimeout(LedIdleTimeout),
+}
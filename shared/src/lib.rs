@@ -1,7 +1,10 @@
 #![cfg_attr(target_arch = "arm", no_std)]
 
 pub mod cmd;
+pub mod debounce;
 pub mod device_to_host;
 pub mod hid;
 pub mod host_to_device;
+pub mod rgb;
 pub mod side;
+pub mod switches;
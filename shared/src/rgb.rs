@@ -0,0 +1,330 @@
+//! Settings for the RGB lighting that the host can change
+
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PerlinColour {
+    /// Cycle through the rainbow
+    Random,
+    Single(Rgb),
+    /// Blend between two colours
+    Double(Rgb, Rgb),
+}
+
+/// How a ripple animation looks
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RippleParams {
+    /// Colour of the rings, or random colours if `None`
+    pub colour: Option<Rgb>,
+    /// How fast rings spread out, in mm per second
+    pub speed: u16,
+    /// How long a ring takes to fade away, in milliseconds
+    pub decay_ms: u16,
+}
+
+impl RippleParams {
+    pub const DEFAULT: Self = Self {
+        colour: None,
+        speed: 150,
+        decay_ms: 1200,
+    };
+}
+
+impl Default for RippleParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// An animation to show and its parameters
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AnimationChoice {
+    Off,
+    Snow,
+    Perlin(PerlinColour),
+    /// Splashes of a single colour, or random colours if `None`
+    Rain(Option<Rgb>),
+    /// Rings spreading out from each key press, across both sides
+    Ripple(RippleParams),
+}
+
+impl AnimationChoice {
+    /// The next animation along, with default parameters
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Snow,
+            Self::Snow => Self::Perlin(PerlinColour::Random),
+            Self::Perlin(_) => Self::Rain(None),
+            Self::Rain(_) => Self::Ripple(RippleParams::DEFAULT),
+            Self::Ripple(_) => Self::Off,
+        }
+    }
+}
+
+/// How likely each animation is to be picked by a playlist, animations with a
+/// weight of zero are never picked
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PlaylistWeights {
+    pub snow: u8,
+    pub perlin: u8,
+    pub rain: u8,
+    pub ripple: u8,
+}
+
+/// How the animation changes by itself
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RandomizerPolicy {
+    /// Keep showing the chosen animation
+    Off,
+    /// Pick a random animation every `minutes`
+    Interval { minutes: u16 },
+    /// Pick an animation every `minutes`, weighted by `weights`
+    Playlist {
+        minutes: u16,
+        weights: PlaylistWeights,
+    },
+}
+
+impl RandomizerPolicy {
+    pub const DEFAULT: Self = Self::Interval { minutes: 5 };
+}
+
+impl Default for RandomizerPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The most current the LEDs on each side may draw, frames that would draw
+/// more are dimmed to fit
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PowerBudget {
+    /// Budget while powered over USB, in milliamps
+    pub usb_ma: u16,
+    /// Budget while running from the battery, in milliamps
+    pub battery_ma: u16,
+}
+
+impl PowerBudget {
+    pub const DEFAULT: Self = Self {
+        usb_ma: 400,
+        battery_ma: 150,
+    };
+}
+
+impl Default for PowerBudget {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// How much to scale a frame's colours by, out of 256, so a frame estimated to
+/// draw `estimate_ua` fits in `budget_ma`
+///
+/// `idle_ua` is what the LEDs draw with every channel off, which can't be
+/// scaled away, so only the lit part of the estimate counts. A budget that
+/// doesn't even cover the idle draw turns everything off.
+pub fn power_scale(budget_ma: u16, estimate_ua: u32, idle_ua: u32) -> u16 {
+    let budget_ua = budget_ma as u32 * 1000;
+
+    if estimate_ua <= budget_ua {
+        return 256;
+    }
+
+    let lit = estimate_ua.saturating_sub(idle_ua);
+    let allowed = budget_ua.saturating_sub(idle_ua);
+
+    allowed
+        .checked_mul(256)
+        .and_then(|v| v.checked_div(lit))
+        .unwrap_or(0)
+        .min(256) as u16
+}
+
+/// How long the LEDs stay on without any key presses on either side
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LedIdleTimeout {
+    /// Seconds before the LEDs fade out and are powered off, zero keeps them
+    /// on
+    pub seconds: u16,
+}
+
+impl LedIdleTimeout {
+    pub const DEFAULT: Self = Self { seconds: 300 };
+}
+
+impl Default for LedIdleTimeout {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// How a notification from the host is shown
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NotificationEffect {
+    /// Flash every key on and off
+    Flash,
+    /// Fade every key in and out
+    Pulse,
+    /// Fill the number row from left to right, `percent` of the way
+    Progress { percent: u8 },
+}
+
+/// An effect shown over the animation for a while, for things like failed
+/// builds, incoming calls or timers
+#[derive(
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    postcard::experimental::max_size::MaxSize,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Notification {
+    pub effect: NotificationEffect,
+    pub colour: Rgb,
+    /// How long to show it for, zero clears any notification being shown
+    pub seconds: u16,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDLE_UA: u32 = 40 * 1_000;
+
+    #[test]
+    fn within_budget_is_unscaled() {
+        assert_eq!(power_scale(400, 300_000, IDLE_UA), 256);
+        assert_eq!(power_scale(400, 400_000, IDLE_UA), 256);
+    }
+
+    #[test]
+    fn over_budget_scales_the_lit_part() {
+        // 240mA lit, 160mA of it allowed
+        assert_eq!(power_scale(200, 280_000, IDLE_UA), 256 * 2 / 3);
+    }
+
+    #[test]
+    fn black_frame_with_a_tiny_budget() {
+        assert_eq!(power_scale(0, IDLE_UA, IDLE_UA), 0);
+        assert_eq!(power_scale(10, IDLE_UA, IDLE_UA), 0);
+    }
+
+    #[test]
+    fn budget_below_idle_turns_everything_off() {
+        assert_eq!(power_scale(0, 500_000, IDLE_UA), 0);
+        assert_eq!(power_scale(39, 500_000, IDLE_UA), 0);
+    }
+}
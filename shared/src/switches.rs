@@ -0,0 +1,238 @@
+//! Where every switch on each side is, in one place
+//!
+//! Each side's table is in LED chain order, so a switch's index in the table
+//! is also the index of the LED under it. Everything else that cares about
+//! switch positions (the scanner's raw to logical mapping, the RGB layout,
+//! host tools that draw the board) is derived from these tables, and the
+//! tables are checked when they're built so a mistake is a compile error
+//! rather than a key that lights the wrong LED.
+
+/// Columns and rows of the raw matrix on each side
+pub const RAW_COLS: usize = 7;
+pub const RAW_ROWS: usize = 6;
+
+/// Columns and rows of the keymap, covering both sides
+pub const LAYOUT_COLS: usize = 12;
+pub const LAYOUT_ROWS: usize = 10;
+
+/// Switches (and LEDs) on each side
+pub const NUM_SWITCHES: usize = 40;
+
+#[derive(Clone, Copy)]
+pub struct Switch {
+    /// (column, row) in this side's raw matrix
+    pub raw: (u8, u8),
+
+    /// (column, row) in the keymap
+    pub logical: (u8, u8),
+
+    /// relative distance from the bottom left light on the left board (mm)
+    pub location: (i16, i16),
+}
+
+pub struct Switches {
+    /// In LED order
+    pub switches: [Switch; NUM_SWITCHES],
+
+    /// Index into `switches` by raw (column, row)
+    pub by_raw: [[Option<u8>; RAW_ROWS]; RAW_COLS],
+}
+
+impl Switches {
+    /// The keymap position of the switch at a raw position, if there is one
+    pub fn logical(&self, raw: (u8, u8)) -> Option<(u8, u8)> {
+        let idx = (*self.by_raw.get(raw.0 as usize)?.get(raw.1 as usize)?)?;
+        Some(self.switches[idx as usize].logical)
+    }
+}
+
+const fn index(switches: [Switch; NUM_SWITCHES]) -> Switches {
+    let mut by_raw = [[None; RAW_ROWS]; RAW_COLS];
+    let mut by_logical = [[false; LAYOUT_ROWS]; LAYOUT_COLS];
+
+    let mut i = 0;
+    while i < NUM_SWITCHES {
+        let Switch { raw, logical, .. } = switches[i];
+
+        assert!((raw.0 as usize) < RAW_COLS && (raw.1 as usize) < RAW_ROWS);
+        assert!((logical.0 as usize) < LAYOUT_COLS && (logical.1 as usize) < LAYOUT_ROWS);
+
+        assert!(
+            by_raw[raw.0 as usize][raw.1 as usize].is_none(),
+            "two switches share a raw position"
+        );
+        assert!(
+            !by_logical[logical.0 as usize][logical.1 as usize],
+            "two switches share a keymap position"
+        );
+
+        by_raw[raw.0 as usize][raw.1 as usize] = Some(i as u8);
+        by_logical[logical.0 as usize][logical.1 as usize] = true;
+
+        i += 1;
+    }
+
+    Switches { switches, by_raw }
+}
+
+/// Neither side may use a keymap position the other uses
+const fn check_disjoint(a: &Switches, b: &Switches) {
+    let mut i = 0;
+    while i < NUM_SWITCHES {
+        let mut j = 0;
+        while j < NUM_SWITCHES {
+            let (ax, ay) = a.switches[i].logical;
+            let (bx, by) = b.switches[j].logical;
+            assert!(ax != bx || ay != by, "both sides share a keymap position");
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+const _: () = check_disjoint(&LEFT, &RIGHT);
+
+pub mod left {
+    use super::{index, Switch, Switches};
+
+    /// the top right switch in the left keyboard is offset in the x axis by this much
+    pub const TOP_RIGHT_LED_OFFSET: i16 = 90;
+
+    /// (location x, location y, raw, logical)
+    const fn s(x: i16, y: i16, raw: (u8, u8), logical: (u8, u8)) -> Switch {
+        Switch {
+            raw,
+            logical,
+            location: (TOP_RIGHT_LED_OFFSET - x, y),
+        }
+    }
+
+    // we use the same relative positions as the right side, just flipped and
+    // shifted. the thumb cluster is wired into the last raw column, but lives
+    // on two rows beneath the main keys in the keymap
+
+    #[rustfmt::skip]
+    pub const LEFT: Switches = index([
+        // thumb cluster
+        s(40, 5, (6, 0), (3, 6)),
+        s(20, 0, (6, 1), (4, 6)),
+        s(0, -5, (6, 2), (5, 6)),
+        s(40, -5, (6, 3), (3, 7)),
+        s(20, -15, (6, 4), (4, 7)),
+        s(0, -25, (6, 5), (5, 7)),
+        // col 0
+        s(60, 80, (5, 1), (5, 1)),
+        s(60, 60, (5, 2), (5, 2)),
+        s(60, 40, (5, 3), (5, 3)),
+        s(60, 20, (5, 4), (5, 4)),
+        // col 1
+        s(80, 100, (4, 0), (4, 0)),
+        s(80, 80, (4, 1), (4, 1)),
+        s(80, 60, (4, 2), (4, 2)),
+        s(80, 40, (4, 3), (4, 3)),
+        s(80, 20, (4, 4), (4, 4)),
+        s(80, 0, (4, 5), (4, 5)),
+        // col 2
+        s(100, 100, (3, 0), (3, 0)),
+        s(100, 80, (3, 1), (3, 1)),
+        s(100, 60, (3, 2), (3, 2)),
+        s(100, 40, (3, 3), (3, 3)),
+        s(100, 20, (3, 4), (3, 4)),
+        s(100, 0, (3, 5), (3, 5)),
+        // col 3
+        s(120, 100, (2, 0), (2, 0)),
+        s(120, 80, (2, 1), (2, 1)),
+        s(120, 60, (2, 2), (2, 2)),
+        s(120, 40, (2, 3), (2, 3)),
+        s(120, 20, (2, 4), (2, 4)),
+        s(120, 0, (2, 5), (2, 5)),
+        // col 4
+        s(140, 100, (1, 0), (1, 0)),
+        s(140, 80, (1, 1), (1, 1)),
+        s(140, 60, (1, 2), (1, 2)),
+        s(140, 40, (1, 3), (1, 3)),
+        s(140, 20, (1, 4), (1, 4)),
+        s(140, 0, (1, 5), (1, 5)),
+        // col 5
+        s(160, 100, (0, 0), (0, 0)),
+        s(160, 80, (0, 1), (0, 1)),
+        s(160, 60, (0, 2), (0, 2)),
+        s(160, 40, (0, 3), (0, 3)),
+        s(160, 20, (0, 4), (0, 4)),
+        s(160, 0, (0, 5), (0, 5)),
+    ]);
+}
+
+pub use left::LEFT;
+
+pub mod right {
+    use super::{index, Switch, Switches};
+
+    /// the top left switch in the right keyboard is offset in the x axis by this much
+    pub const RIGHT_LED_OFFSET: i16 = 180;
+
+    /// (location x, location y, raw, logical)
+    const fn s(x: i16, y: i16, raw: (u8, u8), logical: (u8, u8)) -> Switch {
+        Switch {
+            raw,
+            logical,
+            location: (x + RIGHT_LED_OFFSET, y),
+        }
+    }
+
+    // the thumb cluster is wired into the first raw column, everything else is
+    // shifted over to the right half of the keymap
+
+    #[rustfmt::skip]
+    pub const RIGHT: Switches = index([
+        // thumb cluster
+        s(40, 5, (0, 0), (8, 6)),
+        s(20, 0, (0, 1), (7, 6)),
+        s(0, -5, (0, 2), (6, 6)),
+        s(40, -5, (0, 3), (8, 7)),
+        s(20, -15, (0, 4), (7, 7)),
+        s(0, -25, (0, 5), (6, 7)),
+        // col 0
+        s(60, 80, (1, 1), (6, 1)),
+        s(60, 60, (1, 2), (6, 2)),
+        s(60, 40, (1, 3), (6, 3)),
+        s(60, 20, (1, 4), (6, 4)),
+        // col 1
+        s(80, 100, (2, 0), (7, 0)),
+        s(80, 80, (2, 1), (7, 1)),
+        s(80, 60, (2, 2), (7, 2)),
+        s(80, 40, (2, 3), (7, 3)),
+        s(80, 20, (2, 4), (7, 4)),
+        s(80, 0, (2, 5), (7, 5)),
+        // col 2
+        s(100, 100, (3, 0), (8, 0)),
+        s(100, 80, (3, 1), (8, 1)),
+        s(100, 60, (3, 2), (8, 2)),
+        s(100, 40, (3, 3), (8, 3)),
+        s(100, 20, (3, 4), (8, 4)),
+        s(100, 0, (3, 5), (8, 5)),
+        // col 3
+        s(120, 100, (4, 0), (9, 0)),
+        s(120, 80, (4, 1), (9, 1)),
+        s(120, 60, (4, 2), (9, 2)),
+        s(120, 40, (4, 3), (9, 3)),
+        s(120, 20, (4, 4), (9, 4)),
+        s(120, 0, (4, 5), (9, 5)),
+        // col 4
+        s(140, 100, (5, 0), (10, 0)),
+        s(140, 80, (5, 1), (10, 1)),
+        s(140, 60, (5, 2), (10, 2)),
+        s(140, 40, (5, 3), (10, 3)),
+        s(140, 20, (5, 4), (10, 4)),
+        s(140, 0, (5, 5), (10, 5)),
+        // col 5
+        s(160, 100, (6, 0), (11, 0)),
+        s(160, 80, (6, 1), (11, 1)),
+        s(160, 60, (6, 2), (11, 2)),
+        s(160, 40, (6, 3), (11, 3)),
+        s(160, 20, (6, 4), (11, 4)),
+        s(160, 0, (6, 5), (11, 5)),
+    ]);
+}
+
+pub use right::RIGHT;